@@ -0,0 +1,140 @@
+use regex::Regex;
+
+use crate::adapters::user::OwnerResult;
+use crate::entities::acl::{Grant, Grantee, Permission};
+
+const ALL_USERS_URI: &str = "http://acs.amazonaws.com/groups/global/AllUsers";
+const AUTHENTICATED_USERS_URI: &str = "http://acs.amazonaws.com/groups/global/AuthenticatedUsers";
+
+#[derive(Debug)]
+pub struct GrantResult {
+    pub grantee: Grantee,
+    pub display_name: Option<String>,
+    pub permission: Permission,
+}
+
+impl From<&Grant> for GrantResult {
+    fn from(grant: &Grant) -> Self {
+        Self {
+            grantee: grant.grantee.clone(),
+            display_name: None,
+            permission: grant.permission,
+        }
+    }
+}
+
+impl GrantResult {
+    pub fn to_xml(&self) -> String {
+        let grantee = match &self.grantee {
+            Grantee::User(id) => format!(
+                "<Grantee xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:type=\"CanonicalUser\"><ID>{}</ID>{}</Grantee>",
+                id,
+                self.display_name
+                    .as_ref()
+                    .map(|name| format!("<DisplayName>{}</DisplayName>", name))
+                    .unwrap_or_default()
+            ),
+            Grantee::AllUsers => group_xml(ALL_USERS_URI),
+            Grantee::AuthenticatedUsers => group_xml(AUTHENTICATED_USERS_URI),
+        };
+
+        format!(
+            "<Grant>{}<Permission>{}</Permission></Grant>",
+            grantee,
+            permission_name(self.permission)
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct AccessControlPolicy {
+    pub owner: Option<OwnerResult>,
+    pub grants: Vec<GrantResult>,
+}
+
+impl AccessControlPolicy {
+    pub fn to_xml(&self) -> String {
+        format!(
+            "<AccessControlPolicy xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">{}<AccessControlList>{}</AccessControlList></AccessControlPolicy>",
+            self.owner.as_ref().map(|o| o.to_xml()).unwrap_or_default(),
+            self.grants
+                .iter()
+                .map(|g| g.to_xml())
+                .collect::<Vec<String>>()
+                .join("")
+        )
+    }
+
+    pub fn from_xml(xml: &str) -> Option<Self> {
+        let policy_re =
+            Regex::new(r"(?s)<AccessControlPolicy[^>]*>(.*)</AccessControlPolicy>").unwrap();
+        let owner_re = Regex::new(r"(?s)<Owner>.*?<ID>\s*(.*?)\s*</ID>.*?</Owner>").unwrap();
+        let grant_re = Regex::new(r"(?s)<Grant>(.*?)</Grant>").unwrap();
+        let type_re = Regex::new(r#"xsi:type\s*=\s*"(\w+)""#).unwrap();
+        let id_re = Regex::new(r"<ID>\s*(.*?)\s*</ID>").unwrap();
+        let uri_re = Regex::new(r"<URI>\s*(.*?)\s*</URI>").unwrap();
+        let permission_re = Regex::new(r"<Permission>\s*(\w+)\s*</Permission>").unwrap();
+
+        let policy = policy_re.captures(xml)?.get(1)?.as_str();
+        let grants = grant_re
+            .captures_iter(policy)
+            .map(|grant| {
+                let grant = grant.get(1)?.as_str();
+                let grantee = match type_re.captures(grant)?.get(1)?.as_str() {
+                    "CanonicalUser" => {
+                        Grantee::User(id_re.captures(grant)?.get(1)?.as_str().to_string())
+                    }
+                    "Group" => match uri_re.captures(grant)?.get(1)?.as_str() {
+                        ALL_USERS_URI => Grantee::AllUsers,
+                        AUTHENTICATED_USERS_URI => Grantee::AuthenticatedUsers,
+                        _ => return None,
+                    },
+                    _ => return None,
+                };
+                let permission = parse_permission(permission_re.captures(grant)?.get(1)?.as_str())?;
+
+                Some(GrantResult {
+                    grantee,
+                    display_name: None,
+                    permission,
+                })
+            })
+            .collect::<Option<Vec<GrantResult>>>()?;
+
+        Some(Self {
+            owner: owner_re.captures(policy).map(|owner| OwnerResult {
+                id: owner[1].to_string(),
+                display_name: String::new(),
+            }),
+            grants,
+        })
+    }
+}
+
+fn group_xml(uri: &str) -> String {
+    format!(
+        "<Grantee xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:type=\"Group\"><URI>{}</URI></Grantee>",
+        uri
+    )
+}
+
+fn permission_name(permission: Permission) -> &'static str {
+    match permission {
+        Permission::FullControl => "FULL_CONTROL",
+        Permission::Read => "READ",
+        Permission::Write => "WRITE",
+        Permission::ReadAcp => "READ_ACP",
+        Permission::WriteAcp => "WRITE_ACP",
+    }
+}
+
+fn parse_permission(permission: &str) -> Option<Permission> {
+    match permission {
+        "FULL_CONTROL" => Some(Permission::FullControl),
+        "READ" => Some(Permission::Read),
+        "WRITE" => Some(Permission::Write),
+        "READ_ACP" => Some(Permission::ReadAcp),
+        "WRITE_ACP" => Some(Permission::WriteAcp),
+        _ => None,
+    }
+}
@@ -1,7 +1,8 @@
 use chrono::{DateTime, Local};
+use regex::Regex;
 
 use crate::adapters::user::OwnerResult;
-use crate::entities::bucket::Bucket;
+use crate::entities::bucket::{Bucket, Versioning};
 
 #[derive(Debug)]
 pub struct BucketResult {
@@ -46,3 +47,78 @@ impl ListAllMyBucketsResult {
         )
     }
 }
+
+#[derive(Debug)]
+pub struct CreateBucketConfiguration {
+    pub location: Option<String>,
+}
+
+impl CreateBucketConfiguration {
+    pub fn from_xml(xml: &str) -> Option<Self> {
+        let configuration_re =
+            Regex::new(r"(?s)<CreateBucketConfiguration[^>]*>(.*)</CreateBucketConfiguration>")
+                .unwrap();
+        let location_re =
+            Regex::new(r"(?s)<LocationConstraint>\s*(.*?)\s*</LocationConstraint>").unwrap();
+
+        let configuration = configuration_re.captures(xml)?.get(1)?.as_str();
+        Some(Self {
+            location: location_re
+                .captures(configuration)
+                .map(|location| location[1].to_string())
+                .filter(|location| !location.is_empty()),
+        })
+    }
+}
+
+// buckets created without a location constraint are in us-east-1, which
+// S3 reports as an empty constraint
+#[derive(Debug)]
+pub struct LocationConstraint {
+    pub location: Option<String>,
+}
+
+impl LocationConstraint {
+    pub fn to_xml(&self) -> String {
+        format!(
+            "<LocationConstraint xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">{}</LocationConstraint>",
+            self.location.as_deref().unwrap_or("")
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct VersioningConfiguration {
+    pub versioning: Versioning,
+}
+
+impl VersioningConfiguration {
+    pub fn to_xml(&self) -> String {
+        let status = match self.versioning {
+            Versioning::Unversioned => "".to_string(),
+            Versioning::Enabled => "<Status>Enabled</Status>".to_string(),
+            Versioning::Suspended => "<Status>Suspended</Status>".to_string(),
+        };
+
+        format!(
+            "<VersioningConfiguration xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">{}</VersioningConfiguration>",
+            status
+        )
+    }
+
+    // a bucket can't go back to being unversioned, so a status is required
+    pub fn from_xml(xml: &str) -> Option<Self> {
+        let status_re = Regex::new(
+            r"(?s)<VersioningConfiguration[^>]*>.*<Status>\s*(\w+)\s*</Status>.*</VersioningConfiguration>",
+        )
+        .unwrap();
+
+        let versioning = match status_re.captures(xml)?.get(1)?.as_str() {
+            "Enabled" => Versioning::Enabled,
+            "Suspended" => Versioning::Suspended,
+            _ => return None,
+        };
+
+        Some(Self { versioning })
+    }
+}
@@ -0,0 +1,122 @@
+use crate::adapters::xml_escape;
+use crate::entities::error::Error;
+
+#[derive(Debug)]
+pub struct ErrorResult {
+    pub status: u16,
+    pub code: String,
+    pub message: String,
+    pub resource: String,
+    pub request_id: String,
+}
+
+impl ErrorResult {
+    pub fn new(error: &Error, resource: &str, request_id: &str) -> Self {
+        let status = match error {
+            Error::NotModified => 304,
+            Error::AuthorizationHeaderMalformed
+            | Error::AuthorizationQueryParametersError(_)
+            | Error::BadDigest
+            | Error::IncompleteBody
+            | Error::InvalidArgument(_)
+            | Error::InvalidBucketName
+            | Error::InvalidLocationConstraint
+            | Error::InvalidPart
+            | Error::InvalidPartOrder
+            | Error::InvalidRequest(_)
+            | Error::KeyTooLongError
+            | Error::MalformedACLError
+            | Error::MalformedPolicy(_)
+            | Error::MalformedXML
+            | Error::MetadataTooLarge
+            | Error::XAmzContentSHA256Mismatch => 400,
+            Error::AccessDenied
+            | Error::InvalidAccessKeyId
+            | Error::RequestNotYetValid
+            | Error::RequestTimeTooSkewed
+            | Error::SignatureDoesNotMatch => 403,
+            Error::NoSuchBucket
+            | Error::NoSuchBucketPolicy
+            | Error::NoSuchKey
+            | Error::NoSuchLifecycleConfiguration
+            | Error::NoSuchUpload
+            | Error::NoSuchVersion => 404,
+            Error::MethodNotAllowed => 405,
+            Error::BucketAlreadyExists | Error::BucketAlreadyOwnedByYou | Error::BucketNotEmpty => {
+                409
+            }
+            Error::PreconditionFailed => 412,
+            Error::InvalidPartNumber | Error::InvalidRange => 416,
+            Error::InternalError(_) => 500,
+        };
+
+        Self {
+            status,
+            code: error.code().to_string(),
+            message: error.message(),
+            resource: resource.to_string(),
+            request_id: request_id.to_string(),
+        }
+    }
+
+    pub fn to_xml(&self) -> String {
+        format!(
+            "<Error><Code>{}</Code><Message>{}</Message><Resource>{}</Resource><RequestId>{}</RequestId></Error>",
+            self.code,
+            xml_escape(&self.message),
+            xml_escape(&self.resource),
+            self.request_id
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_map_to_their_status() {
+        for (error, status) in [
+            (Error::NotModified, 304),
+            (Error::InvalidArgument("bad".to_string()), 400),
+            (Error::MalformedXML, 400),
+            (Error::KeyTooLongError, 400),
+            (Error::AccessDenied, 403),
+            (Error::SignatureDoesNotMatch, 403),
+            (Error::RequestNotYetValid, 403),
+            (Error::RequestTimeTooSkewed, 403),
+            (Error::NoSuchBucket, 404),
+            (Error::NoSuchKey, 404),
+            (Error::MethodNotAllowed, 405),
+            (Error::BucketAlreadyOwnedByYou, 409),
+            (Error::BucketNotEmpty, 409),
+            (Error::PreconditionFailed, 412),
+            (Error::InvalidRange, 416),
+            (Error::InternalError("disk full".to_string()), 500),
+        ] {
+            let result = ErrorResult::new(&error, "/bucket", "ID");
+            assert_eq!(result.status, status, "{}", error);
+            assert_eq!(result.code, error.code());
+        }
+    }
+
+    #[test]
+    fn error_xml_escapes_message_and_resource() {
+        let error = Error::InvalidArgument("<x> & 'y'".to_string());
+        let xml = ErrorResult::new(&error, "/bucket/a&b<c", "ID").to_xml();
+
+        assert_eq!(
+            xml,
+            "<Error><Code>InvalidArgument</Code><Message>&lt;x&gt; &amp; &apos;y&apos;</Message><Resource>/bucket/a&amp;b&lt;c</Resource><RequestId>ID</RequestId></Error>"
+        );
+    }
+
+    #[test]
+    fn internal_errors_hide_their_cause() {
+        let error = Error::InternalError("/var/data/secret: disk full".to_string());
+        let xml = ErrorResult::new(&error, "/bucket", "ID").to_xml();
+
+        assert!(!xml.contains("secret"));
+        assert!(xml.contains("<Code>InternalError</Code>"));
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+
+use regex::Regex;
+
+use crate::adapters::{xml_escape, xml_unescape};
+use crate::entities::lifecycle::{Lifecycle, Rule};
+
+// about a thousand years, well inside the range of a timestamp
+const MAX_DAYS: i64 = 365_000;
+
+#[derive(Debug)]
+pub struct LifecycleConfiguration {
+    pub lifecycle: Lifecycle,
+}
+
+impl From<&Lifecycle> for LifecycleConfiguration {
+    fn from(lifecycle: &Lifecycle) -> Self {
+        Self {
+            lifecycle: lifecycle.clone(),
+        }
+    }
+}
+
+impl LifecycleConfiguration {
+    pub fn to_xml(&self) -> String {
+        format!(
+            "<LifecycleConfiguration xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">{}</LifecycleConfiguration>",
+            self.lifecycle
+                .rules
+                .iter()
+                .map(rule_xml)
+                .collect::<Vec<String>>()
+                .join("")
+        )
+    }
+
+    // rules without an action, or with an action this server can't apply
+    // like a fixed expiration date, are rejected as a whole
+    pub fn from_xml(xml: &str) -> Option<Self> {
+        let configuration_re =
+            Regex::new(r"(?s)<LifecycleConfiguration[^>]*>(.*)</LifecycleConfiguration>").unwrap();
+        let rule_re = Regex::new(r"(?s)<Rule>(.*?)</Rule>").unwrap();
+        let id_re = Regex::new(r"(?s)<ID>(.*?)</ID>").unwrap();
+        let status_re = Regex::new(r"<Status>\s*(\w+)\s*</Status>").unwrap();
+        let prefix_re = Regex::new(r"(?s)<Prefix>(.*?)</Prefix>").unwrap();
+        let tag_re =
+            Regex::new(r"(?s)<Tag>\s*<Key>(.*?)</Key>\s*<Value>(.*?)</Value>\s*</Tag>").unwrap();
+        let expiration_re = Regex::new(r"(?s)<Expiration>(.*?)</Expiration>").unwrap();
+        let days_re = Regex::new(r"^\s*<Days>\s*(\d+)\s*</Days>\s*$").unwrap();
+        let noncurrent_re = Regex::new(
+            r"(?s)<NoncurrentVersionExpiration>\s*<NoncurrentDays>\s*(\d+)\s*</NoncurrentDays>\s*</NoncurrentVersionExpiration>",
+        )
+        .unwrap();
+        let abort_re = Regex::new(
+            r"(?s)<AbortIncompleteMultipartUpload>\s*<DaysAfterInitiation>\s*(\d+)\s*</DaysAfterInitiation>\s*</AbortIncompleteMultipartUpload>",
+        )
+        .unwrap();
+
+        let configuration = configuration_re.captures(xml)?.get(1)?.as_str();
+        let rules = rule_re
+            .captures_iter(configuration)
+            .map(|rule| {
+                let rule = rule.get(1)?.as_str();
+                let days = |re: &Regex, xml: &str| match re.captures(xml) {
+                    Some(days) => days[1]
+                        .parse()
+                        .ok()
+                        .filter(|d| (1..=MAX_DAYS).contains(d))
+                        .map(Some),
+                    None => Some(None),
+                };
+                // an Expiration without Days sets a date or cleans up delete
+                // markers, neither of which is supported
+                let expiration_days = match expiration_re.captures(rule) {
+                    Some(expiration) => Some(days(&days_re, &expiration[1])??),
+                    None => None,
+                };
+
+                let rule = Rule {
+                    id: id_re.captures(rule).map(|id| xml_unescape(&id[1])),
+                    enabled: match status_re.captures(rule)?.get(1)?.as_str() {
+                        "Enabled" => true,
+                        "Disabled" => false,
+                        _ => return None,
+                    },
+                    prefix: prefix_re
+                        .captures(rule)
+                        .map(|prefix| xml_unescape(&prefix[1]))
+                        .unwrap_or_default(),
+                    tags: tag_re
+                        .captures_iter(rule)
+                        .map(|tag| (xml_unescape(&tag[1]), xml_unescape(&tag[2])))
+                        .collect(),
+                    expiration_days,
+                    noncurrent_expiration_days: days(&noncurrent_re, rule)?,
+                    abort_incomplete_upload_days: days(&abort_re, rule)?,
+                };
+
+                match rule.expiration_days.is_some()
+                    || rule.noncurrent_expiration_days.is_some()
+                    || rule.abort_incomplete_upload_days.is_some()
+                {
+                    true => Some(rule),
+                    false => None,
+                }
+            })
+            .collect::<Option<Vec<Rule>>>()?;
+
+        if rules.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            lifecycle: Lifecycle { rules },
+        })
+    }
+}
+
+fn rule_xml(rule: &Rule) -> String {
+    let days = |tag: &str, days: Option<i64>, inner: &str| {
+        days.map(|d| format!("<{}><{}>{}</{}></{}>", tag, inner, d, inner, tag))
+            .unwrap_or_default()
+    };
+
+    format!(
+        "<Rule>{}<Filter>{}</Filter><Status>{}</Status>{}{}{}</Rule>",
+        rule.id
+            .as_ref()
+            .map(|id| format!("<ID>{}</ID>", xml_escape(id)))
+            .unwrap_or_default(),
+        filter_xml(&rule.prefix, &rule.tags),
+        if rule.enabled { "Enabled" } else { "Disabled" },
+        days("Expiration", rule.expiration_days, "Days"),
+        days(
+            "NoncurrentVersionExpiration",
+            rule.noncurrent_expiration_days,
+            "NoncurrentDays"
+        ),
+        days(
+            "AbortIncompleteMultipartUpload",
+            rule.abort_incomplete_upload_days,
+            "DaysAfterInitiation"
+        ),
+    )
+}
+
+fn filter_xml(prefix: &str, tags: &BTreeMap<String, String>) -> String {
+    let tags_xml = tags
+        .iter()
+        .map(|(key, value)| {
+            format!(
+                "<Tag><Key>{}</Key><Value>{}</Value></Tag>",
+                xml_escape(key),
+                xml_escape(value)
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("");
+
+    let prefix = xml_escape(prefix);
+
+    match tags.len() {
+        0 => format!("<Prefix>{}</Prefix>", prefix),
+        1 if prefix.is_empty() => tags_xml,
+        _ => format!("<And><Prefix>{}</Prefix>{}</And>", prefix, tags_xml),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_round_trip_through_escaped_xml() {
+        let rule = Rule {
+            id: Some("a&b<c>".to_string()),
+            enabled: true,
+            prefix: "logs/<&>\"'".to_string(),
+            tags: vec![
+                ("k&1".to_string(), "v<1>".to_string()),
+                ("k\"2".to_string(), "v'2".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+            expiration_days: Some(30),
+            noncurrent_expiration_days: None,
+            abort_incomplete_upload_days: None,
+        };
+        let xml = LifecycleConfiguration::from(&Lifecycle {
+            rules: vec![rule.clone()],
+        })
+        .to_xml();
+        assert!(xml.contains("<ID>a&amp;b&lt;c&gt;</ID>"));
+        assert!(xml.contains("<Prefix>logs/&lt;&amp;&gt;&quot;&apos;</Prefix>"));
+
+        let parsed = LifecycleConfiguration::from_xml(&xml).unwrap().lifecycle;
+        assert_eq!(parsed.rules.len(), 1);
+        assert_eq!(parsed.rules[0].id, rule.id);
+        assert_eq!(parsed.rules[0].prefix, rule.prefix);
+        assert_eq!(parsed.rules[0].tags, rule.tags);
+        assert_eq!(parsed.rules[0].expiration_days, Some(30));
+    }
+}
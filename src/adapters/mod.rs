@@ -1,3 +1,42 @@
+use regex::{Captures, Regex};
+
+pub mod acl;
 pub mod bucket;
+pub mod error;
+pub mod lifecycle;
 pub mod object;
+pub mod policy;
+pub mod upload;
 pub mod user;
+
+pub fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// references that don't name a character are left as they are
+pub fn xml_unescape(value: &str) -> String {
+    let reference_re = Regex::new(r"&(lt|gt|quot|apos|amp|#[0-9]+|#x[0-9a-fA-F]+);").unwrap();
+
+    reference_re
+        .replace_all(value, |reference: &Captures| {
+            let character = match &reference[1] {
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "amp" => Some('&'),
+                code => match code.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => code[1..].parse().ok(),
+                }
+                .and_then(char::from_u32),
+            };
+            character.map_or_else(|| reference[0].to_string(), String::from)
+        })
+        .into_owned()
+}
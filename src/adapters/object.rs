@@ -1,6 +1,8 @@
 use chrono::{DateTime, Local};
+use regex::Regex;
 
 use crate::adapters::user::OwnerResult;
+use crate::adapters::{xml_escape, xml_unescape};
 use crate::entities::object::Object;
 
 #[derive(Debug)]
@@ -15,7 +17,7 @@ pub struct ObjectResult {
 impl From<&Object> for ObjectResult {
     fn from(object: &Object) -> Self {
         Self {
-            etag: "".to_string(),
+            etag: format!("\"{}\"", object.etag),
             key: object.key.to_string(),
             owner: OwnerResult {
                 id: object.owner_id.to_string(),
@@ -32,7 +34,7 @@ impl ObjectResult {
         format!(
             "<Contents><ETag>{}</ETag><Key>{}</Key>{}<Size>{}</Size><LastModified>{:?}</LastModified></Contents>",
             self.etag,
-            self.key,
+            xml_escape(&self.key),
             self.owner.to_xml(),
             self.size,
             self.last_modified,
@@ -40,24 +42,430 @@ impl ObjectResult {
     }
 }
 
+#[derive(Debug)]
+pub struct CopyObjectResult {
+    etag: String,
+    last_modified: DateTime<Local>,
+}
+
+impl From<&Object> for CopyObjectResult {
+    fn from(object: &Object) -> Self {
+        Self {
+            etag: format!("\"{}\"", object.etag),
+            last_modified: object.last_modified,
+        }
+    }
+}
+
+impl CopyObjectResult {
+    pub fn to_xml(&self) -> String {
+        format!(
+            "<CopyObjectResult><LastModified>{:?}</LastModified><ETag>{}</ETag></CopyObjectResult>",
+            self.last_modified, self.etag
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct CommonPrefixResult {
+    pub prefix: String,
+}
+
+impl CommonPrefixResult {
+    pub fn to_xml(&self) -> String {
+        format!(
+            "<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>",
+            xml_escape(&self.prefix)
+        )
+    }
+}
+
+fn optional_xml(tag: &str, value: &Option<String>) -> String {
+    value
+        .as_ref()
+        .map(|v| format!("<{}>{}</{}>", tag, xml_escape(v), tag))
+        .unwrap_or_default()
+}
+
 #[derive(Debug)]
 pub struct ListBucketResult {
     pub is_truncated: bool,
     pub contents: Vec<ObjectResult>,
+    pub common_prefixes: Vec<CommonPrefixResult>,
     pub name: String,
+    pub prefix: String,
+    pub delimiter: Option<String>,
+    pub max_keys: usize,
+    pub marker: Option<String>,
+    pub next_marker: Option<String>,
 }
 
 impl ListBucketResult {
     pub fn to_xml(&self) -> String {
         format!(
-            "<ListBucketResult><IsTruncated>{}</IsTruncated>{}<Name>{}</Name></ListBucketResult>",
+            "<ListBucketResult><IsTruncated>{}</IsTruncated>{}{}{}{}<Name>{}</Name><Prefix>{}</Prefix>{}<MaxKeys>{}</MaxKeys></ListBucketResult>",
             self.is_truncated,
+            optional_xml("Marker", &self.marker),
+            optional_xml("NextMarker", &self.next_marker),
             self.contents
                 .iter()
                 .map(|c| c.to_xml())
                 .collect::<Vec<String>>()
                 .join(""),
-            self.name
+            self.common_prefixes
+                .iter()
+                .map(|c| c.to_xml())
+                .collect::<Vec<String>>()
+                .join(""),
+            xml_escape(&self.name),
+            xml_escape(&self.prefix),
+            optional_xml("Delimiter", &self.delimiter),
+            self.max_keys,
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct ListBucketResultV2 {
+    pub is_truncated: bool,
+    pub contents: Vec<ObjectResult>,
+    pub common_prefixes: Vec<CommonPrefixResult>,
+    pub name: String,
+    pub prefix: String,
+    pub delimiter: Option<String>,
+    pub max_keys: usize,
+    pub key_count: usize,
+    pub continuation_token: Option<String>,
+    pub next_continuation_token: Option<String>,
+    pub start_after: Option<String>,
+}
+
+impl ListBucketResultV2 {
+    pub fn to_xml(&self) -> String {
+        format!(
+            "<ListBucketResult><IsTruncated>{}</IsTruncated>{}{}<Name>{}</Name><Prefix>{}</Prefix>{}<MaxKeys>{}</MaxKeys><KeyCount>{}</KeyCount>{}{}{}</ListBucketResult>",
+            self.is_truncated,
+            self.contents
+                .iter()
+                .map(|c| c.to_xml())
+                .collect::<Vec<String>>()
+                .join(""),
+            self.common_prefixes
+                .iter()
+                .map(|c| c.to_xml())
+                .collect::<Vec<String>>()
+                .join(""),
+            xml_escape(&self.name),
+            xml_escape(&self.prefix),
+            optional_xml("Delimiter", &self.delimiter),
+            self.max_keys,
+            self.key_count,
+            optional_xml("ContinuationToken", &self.continuation_token),
+            optional_xml("NextContinuationToken", &self.next_continuation_token),
+            optional_xml("StartAfter", &self.start_after),
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct VersionResult {
+    etag: String,
+    key: String,
+    version_id: String,
+    pub is_latest: bool,
+    delete_marker: bool,
+    owner: OwnerResult,
+    size: i64,
+    last_modified: DateTime<Local>,
+}
+
+impl From<&Object> for VersionResult {
+    fn from(object: &Object) -> Self {
+        Self {
+            etag: format!("\"{}\"", object.etag),
+            key: object.key.to_string(),
+            version_id: object.version().to_string(),
+            is_latest: false,
+            delete_marker: object.delete_marker,
+            owner: OwnerResult {
+                id: object.owner_id.to_string(),
+                display_name: "".to_string(),
+            },
+            size: object.size,
+            last_modified: object.last_modified,
+        }
+    }
+}
+
+impl VersionResult {
+    pub fn to_xml(&self) -> String {
+        if self.delete_marker {
+            return format!(
+                "<DeleteMarker><Key>{}</Key><VersionId>{}</VersionId><IsLatest>{}</IsLatest><LastModified>{:?}</LastModified>{}</DeleteMarker>",
+                xml_escape(&self.key),
+                self.version_id,
+                self.is_latest,
+                self.last_modified,
+                self.owner.to_xml(),
+            );
+        }
+
+        format!(
+            "<Version><Key>{}</Key><VersionId>{}</VersionId><IsLatest>{}</IsLatest><LastModified>{:?}</LastModified><ETag>{}</ETag><Size>{}</Size>{}<StorageClass>STANDARD</StorageClass></Version>",
+            xml_escape(&self.key),
+            self.version_id,
+            self.is_latest,
+            self.last_modified,
+            self.etag,
+            self.size,
+            self.owner.to_xml(),
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct Delete {
+    pub quiet: bool,
+    pub objects: Vec<(String, Option<String>)>,
+}
+
+impl Delete {
+    pub fn from_xml(xml: &str) -> Option<Self> {
+        let quiet_re = Regex::new(r"<Quiet>\s*(true|false)\s*</Quiet>").unwrap();
+        let object_re = Regex::new(r"(?s)<Object>(.*?)</Object>").unwrap();
+        let key_re = Regex::new(r"(?s)<Key>(.*?)</Key>").unwrap();
+        let version_id_re = Regex::new(r"<VersionId>\s*(.*?)\s*</VersionId>").unwrap();
+
+        let objects = object_re
+            .captures_iter(xml)
+            .map(|object| {
+                let object = object.get(1)?.as_str();
+                let key = xml_unescape(key_re.captures(object)?.get(1)?.as_str());
+                if key.is_empty() {
+                    return None;
+                }
+                let version_id = version_id_re
+                    .captures(object)
+                    .map(|version_id| xml_unescape(&version_id[1]));
+                Some((key, version_id))
+            })
+            .collect::<Option<Vec<(String, Option<String>)>>>()?;
+        if objects.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            quiet: quiet_re
+                .captures(xml)
+                .is_some_and(|quiet| &quiet[1] == "true"),
+            objects,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct DeletedResult {
+    key: String,
+    version_id: Option<String>,
+    delete_marker_version_id: Option<String>,
+}
+
+impl DeletedResult {
+    // `version_id` is the version the request named and `object` what its
+    // deletion removed or, without a version, the delete marker it created
+    pub fn new(key: &str, version_id: Option<&str>, object: Option<&Object>) -> Self {
+        Self {
+            key: key.to_string(),
+            version_id: version_id.map(|v| v.to_string()),
+            delete_marker_version_id: object
+                .filter(|o| o.delete_marker)
+                .map(|o| o.version().to_string()),
+        }
+    }
+
+    pub fn to_xml(&self) -> String {
+        let delete_marker = match &self.delete_marker_version_id {
+            Some(version_id) => format!(
+                "<DeleteMarker>true</DeleteMarker><DeleteMarkerVersionId>{}</DeleteMarkerVersionId>",
+                version_id
+            ),
+            None => "".to_string(),
+        };
+
+        format!(
+            "<Deleted><Key>{}</Key>{}{}</Deleted>",
+            xml_escape(&self.key),
+            optional_xml("VersionId", &self.version_id),
+            delete_marker,
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct DeleteErrorResult {
+    pub key: String,
+    pub version_id: Option<String>,
+    pub code: String,
+    pub message: String,
+}
+
+impl DeleteErrorResult {
+    pub fn to_xml(&self) -> String {
+        format!(
+            "<Error><Key>{}</Key>{}<Code>{}</Code><Message>{}</Message></Error>",
+            xml_escape(&self.key),
+            optional_xml("VersionId", &self.version_id),
+            self.code,
+            xml_escape(&self.message),
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DeleteResult {
+    pub deleted: Vec<DeletedResult>,
+    pub errors: Vec<DeleteErrorResult>,
+}
+
+impl DeleteResult {
+    pub fn to_xml(&self) -> String {
+        format!(
+            "<DeleteResult>{}{}</DeleteResult>",
+            self.deleted
+                .iter()
+                .map(|d| d.to_xml())
+                .collect::<Vec<String>>()
+                .join(""),
+            self.errors
+                .iter()
+                .map(|e| e.to_xml())
+                .collect::<Vec<String>>()
+                .join(""),
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct ListVersionsResult {
+    pub is_truncated: bool,
+    pub versions: Vec<VersionResult>,
+    pub common_prefixes: Vec<CommonPrefixResult>,
+    pub name: String,
+    pub prefix: String,
+    pub delimiter: Option<String>,
+    pub max_keys: usize,
+    pub key_marker: Option<String>,
+    pub version_id_marker: Option<String>,
+    pub next_key_marker: Option<String>,
+    pub next_version_id_marker: Option<String>,
+}
+
+impl ListVersionsResult {
+    pub fn to_xml(&self) -> String {
+        format!(
+            "<ListVersionsResult><IsTruncated>{}</IsTruncated>{}{}{}{}{}{}<Name>{}</Name><Prefix>{}</Prefix>{}<MaxKeys>{}</MaxKeys></ListVersionsResult>",
+            self.is_truncated,
+            optional_xml("KeyMarker", &self.key_marker),
+            optional_xml("VersionIdMarker", &self.version_id_marker),
+            optional_xml("NextKeyMarker", &self.next_key_marker),
+            optional_xml("NextVersionIdMarker", &self.next_version_id_marker),
+            self.versions
+                .iter()
+                .map(|v| v.to_xml())
+                .collect::<Vec<String>>()
+                .join(""),
+            self.common_prefixes
+                .iter()
+                .map(|c| c.to_xml())
+                .collect::<Vec<String>>()
+                .join(""),
+            xml_escape(&self.name),
+            xml_escape(&self.prefix),
+            optional_xml("Delimiter", &self.delimiter),
+            self.max_keys,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listing_escapes_user_supplied_values() {
+        let result = ListBucketResultV2 {
+            is_truncated: true,
+            contents: Vec::new(),
+            common_prefixes: vec![CommonPrefixResult {
+                prefix: "a&b<c/".to_string(),
+            }],
+            name: "bucket".to_string(),
+            prefix: "a&b".to_string(),
+            delimiter: Some("/".to_string()),
+            max_keys: 1,
+            key_count: 1,
+            continuation_token: None,
+            next_continuation_token: None,
+            start_after: Some("it's \"quoted\">".to_string()),
+        };
+        let xml = result.to_xml();
+
+        assert!(xml.contains("<Prefix>a&amp;b</Prefix>"));
+        assert!(xml.contains("<Prefix>a&amp;b&lt;c/</Prefix>"));
+        assert!(xml.contains("<StartAfter>it&apos;s &quot;quoted&quot;&gt;</StartAfter>"));
+    }
+
+    #[test]
+    fn version_listing_escapes_keys() {
+        let object = Object {
+            key: "a&b<c".to_string(),
+            bucket: "bucket".to_string(),
+            owner_id: "alice".to_string(),
+            size: 1,
+            last_modified: Local::now(),
+            etag: "etag".to_string(),
+            part_sizes: Vec::new(),
+            acl: Default::default(),
+            tags: Default::default(),
+            metadata: Default::default(),
+            version_id: Some("v1".to_string()),
+            delete_marker: false,
+        };
+        let marker = Object {
+            delete_marker: true,
+            ..object.clone()
+        };
+        let result = ListVersionsResult {
+            is_truncated: false,
+            versions: vec![VersionResult::from(&object), VersionResult::from(&marker)],
+            common_prefixes: Vec::new(),
+            name: "bucket".to_string(),
+            prefix: "a&b".to_string(),
+            delimiter: None,
+            max_keys: 1000,
+            key_marker: Some("<".to_string()),
+            version_id_marker: None,
+            next_key_marker: None,
+            next_version_id_marker: None,
+        };
+        let xml = result.to_xml();
+
+        assert!(xml.contains("<Version><Key>a&amp;b&lt;c</Key>"));
+        assert!(xml.contains("<DeleteMarker><Key>a&amp;b&lt;c</Key>"));
+        assert!(xml.contains("<Prefix>a&amp;b</Prefix>"));
+        assert!(xml.contains("<KeyMarker>&lt;</KeyMarker>"));
+    }
+
+    #[test]
+    fn delete_requests_unescape_keys_and_versions() {
+        let delete = Delete::from_xml(
+            "<Delete><Object><Key>a&#13;b&#x0A;c&amp;#38;</Key><VersionId>v&amp;1</VersionId></Object><Object><Key>&#xD800;&bogus;</Key></Object></Delete>",
         )
+        .unwrap();
+
+        assert_eq!(delete.objects[0].0, "a\rb\nc&#38;");
+        assert_eq!(delete.objects[0].1.as_deref(), Some("v&1"));
+        assert_eq!(delete.objects[1].0, "&#xD800;&bogus;");
+        assert!(Delete::from_xml("<Delete><Object><Key></Key></Object></Delete>").is_none());
     }
 }
@@ -0,0 +1,180 @@
+use serde_json::{json, Map, Value};
+
+use crate::entities::error::Error;
+use crate::entities::policy::{Condition, Effect, Policy, Statement};
+
+#[derive(Debug)]
+pub struct PolicyDocument {
+    pub policy: Policy,
+}
+
+impl From<&Policy> for PolicyDocument {
+    fn from(policy: &Policy) -> Self {
+        Self {
+            policy: policy.clone(),
+        }
+    }
+}
+
+impl PolicyDocument {
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let document: Value = serde_json::from_str(json)
+            .map_err(|_| Error::MalformedPolicy("Policies must be valid JSON".to_string()))?;
+
+        let statements = match &document["Statement"] {
+            Value::Array(statements) => statements.iter().collect(),
+            statement @ Value::Object(_) => vec![statement],
+            _ => return Err(malformed("Missing required field Statement")),
+        };
+
+        Ok(Self {
+            policy: Policy {
+                version: document["Version"].as_str().map(|v| v.to_string()),
+                id: document["Id"].as_str().map(|i| i.to_string()),
+                statements: statements
+                    .into_iter()
+                    .map(statement_from_json)
+                    .collect::<Result<_, _>>()?,
+            },
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut document = Map::new();
+        if let Some(version) = &self.policy.version {
+            document.insert("Version".to_string(), json!(version));
+        }
+        if let Some(id) = &self.policy.id {
+            document.insert("Id".to_string(), json!(id));
+        }
+        document.insert(
+            "Statement".to_string(),
+            self.policy
+                .statements
+                .iter()
+                .map(statement_to_json)
+                .collect(),
+        );
+
+        Value::Object(document).to_string()
+    }
+}
+
+fn malformed(message: &str) -> Error {
+    Error::MalformedPolicy(message.to_string())
+}
+
+fn strings(value: &Value) -> Option<Vec<String>> {
+    match value {
+        Value::String(s) => Some(vec![s.to_string()]),
+        Value::Array(values) => values
+            .iter()
+            .map(|v| v.as_str().map(|s| s.to_string()))
+            .collect(),
+        _ => None,
+    }
+}
+
+fn scalars(value: &Value) -> Option<Vec<String>> {
+    let scalar = |v: &Value| match v {
+        Value::String(s) => Some(s.to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    };
+
+    match value {
+        Value::Array(values) => values.iter().map(scalar).collect(),
+        value => scalar(value).map(|v| vec![v]),
+    }
+}
+
+fn statement_from_json(statement: &Value) -> Result<Statement, Error> {
+    let effect = match statement["Effect"].as_str() {
+        Some("Allow") => Effect::Allow,
+        Some("Deny") => Effect::Deny,
+        _ => return Err(malformed("Invalid effect")),
+    };
+
+    let principals = match &statement["Principal"] {
+        Value::String(p) if p == "*" => vec![p.to_string()],
+        Value::Object(principal) => principal
+            .iter()
+            .map(|(kind, ids)| match kind.as_str() {
+                "AWS" | "CanonicalUser" => strings(ids),
+                _ => None,
+            })
+            .collect::<Option<Vec<Vec<String>>>>()
+            .ok_or_else(|| malformed("Invalid principal in policy"))?
+            .concat(),
+        _ => return Err(malformed("Invalid principal in policy")),
+    };
+
+    let mut conditions = Vec::new();
+    match &statement["Condition"] {
+        Value::Null => {}
+        Value::Object(operators) => {
+            for (operator, keys) in operators {
+                let keys = keys
+                    .as_object()
+                    .ok_or_else(|| malformed("Invalid Condition block"))?;
+                for (key, values) in keys {
+                    conditions.push(Condition {
+                        operator: operator.to_string(),
+                        key: key.to_string(),
+                        values: scalars(values)
+                            .ok_or_else(|| malformed("Invalid Condition value"))?,
+                    });
+                }
+            }
+        }
+        _ => return Err(malformed("Invalid Condition block")),
+    }
+
+    Ok(Statement {
+        sid: statement["Sid"].as_str().map(|s| s.to_string()),
+        effect,
+        principals,
+        actions: strings(&statement["Action"]).ok_or_else(|| malformed("Missing Action"))?,
+        resources: strings(&statement["Resource"]).ok_or_else(|| malformed("Missing Resource"))?,
+        conditions,
+    })
+}
+
+fn statement_to_json(statement: &Statement) -> Value {
+    let mut value = Map::new();
+    if let Some(sid) = &statement.sid {
+        value.insert("Sid".to_string(), json!(sid));
+    }
+    value.insert(
+        "Effect".to_string(),
+        json!(match statement.effect {
+            Effect::Allow => "Allow",
+            Effect::Deny => "Deny",
+        }),
+    );
+    value.insert(
+        "Principal".to_string(),
+        match statement.principals.as_slice() {
+            [p] if p == "*" => json!("*"),
+            principals => json!({ "AWS": principals }),
+        },
+    );
+    value.insert("Action".to_string(), json!(statement.actions));
+    value.insert("Resource".to_string(), json!(statement.resources));
+
+    if !statement.conditions.is_empty() {
+        let mut operators = Map::new();
+        for condition in &statement.conditions {
+            operators
+                .entry(condition.operator.to_string())
+                .or_insert_with(|| json!({}))
+                .as_object_mut()
+                .unwrap()
+                .insert(condition.key.to_string(), json!(condition.values));
+        }
+        value.insert("Condition".to_string(), Value::Object(operators));
+    }
+
+    Value::Object(value)
+}
@@ -0,0 +1,108 @@
+use chrono::{DateTime, Local};
+use regex::Regex;
+
+use crate::adapters::xml_escape;
+use crate::entities::upload::{Part, Upload};
+
+#[derive(Debug)]
+pub struct InitiateMultipartUploadResult {
+    bucket: String,
+    key: String,
+    upload_id: String,
+}
+
+impl From<&Upload> for InitiateMultipartUploadResult {
+    fn from(upload: &Upload) -> Self {
+        Self {
+            bucket: upload.bucket.to_string(),
+            key: upload.key.to_string(),
+            upload_id: upload.id.to_string(),
+        }
+    }
+}
+
+impl InitiateMultipartUploadResult {
+    pub fn to_xml(&self) -> String {
+        format!(
+            "<InitiateMultipartUploadResult><Bucket>{}</Bucket><Key>{}</Key><UploadId>{}</UploadId></InitiateMultipartUploadResult>",
+            xml_escape(&self.bucket),
+            xml_escape(&self.key),
+            self.upload_id
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct CompleteMultipartUploadResult {
+    pub location: String,
+    pub bucket: String,
+    pub key: String,
+    pub etag: String,
+}
+
+impl CompleteMultipartUploadResult {
+    pub fn to_xml(&self) -> String {
+        format!(
+            "<CompleteMultipartUploadResult><Location>{}</Location><Bucket>{}</Bucket><Key>{}</Key><ETag>\"{}\"</ETag></CompleteMultipartUploadResult>",
+            xml_escape(&self.location),
+            xml_escape(&self.bucket),
+            xml_escape(&self.key),
+            self.etag
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct CopyPartResult {
+    etag: String,
+    last_modified: DateTime<Local>,
+}
+
+impl From<&Part> for CopyPartResult {
+    fn from(part: &Part) -> Self {
+        Self {
+            etag: format!("\"{}\"", part.etag),
+            last_modified: part.last_modified,
+        }
+    }
+}
+
+impl CopyPartResult {
+    pub fn to_xml(&self) -> String {
+        format!(
+            "<CopyPartResult><LastModified>{:?}</LastModified><ETag>{}</ETag></CopyPartResult>",
+            self.last_modified, self.etag
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct CompleteMultipartUpload {
+    pub parts: Vec<(i64, String)>,
+}
+
+impl CompleteMultipartUpload {
+    pub fn from_xml(xml: &str) -> Option<Self> {
+        let part_re = Regex::new(r"(?s)<Part>(.*?)</Part>").unwrap();
+        let number_re = Regex::new(r"<PartNumber>\s*(\d+)\s*</PartNumber>").unwrap();
+        let etag_re = Regex::new(r"<ETag>\s*(.*?)\s*</ETag>").unwrap();
+
+        let parts = part_re
+            .captures_iter(xml)
+            .map(|part| {
+                let part = part.get(1)?.as_str();
+                let number = number_re.captures(part)?.get(1)?.as_str().parse().ok()?;
+                let etag = etag_re
+                    .captures(part)?
+                    .get(1)?
+                    .as_str()
+                    .replace("&quot;", "\"")
+                    .trim_matches('"')
+                    .to_string();
+                Some((number, etag))
+            })
+            .collect::<Option<Vec<(i64, String)>>>()?;
+
+        Some(Self { parts })
+    }
+}
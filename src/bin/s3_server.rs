@@ -1,31 +1,48 @@
+use hyper::server::conn::AddrStream;
 use hyper::service::{make_service_fn, service_fn};
 use hyper::Server;
 use std::clone::Clone;
 use std::convert::Infallible;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::Duration;
 
 use anbar::drivers::web_server::App;
+use anbar::interactors::lifecycle;
 use anbar::interactors::storage::Storage;
 
+const LIFECYCLE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
 #[tokio::main]
 async fn main() {
     let addr = SocketAddr::from(([127, 0, 0, 1], 8000));
 
-    let mut s = Storage::new("/home/mehdy/tmp/anbar");
-    s.new_user("mehdy", "Mehdy", "ABC1234", "AbC1Zxv");
-    s.create_bucket("mehdy", "buck");
+    let s = match Storage::new("/home/mehdy/tmp/anbar") {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("failed to open storage: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = s.new_user("mehdy", "Mehdy", "ABC1234", "AbC1Zxv") {
+        eprintln!("skipping user setup: {}", e);
+    }
+    if let Err(e) = s.create_bucket("mehdy", "buck", None).await {
+        eprintln!("skipping bucket setup: {}", e);
+    }
 
-    let storage = Arc::new(Mutex::new(s));
+    let storage = Arc::new(s);
+    tokio::spawn(lifecycle::run(storage.clone(), LIFECYCLE_PERIOD));
 
-    let service = make_service_fn(move |_conn| {
+    let service = make_service_fn(move |conn: &AddrStream| {
         let app = App {
             storage: storage.clone(),
         };
+        let remote_addr = conn.remote_addr();
         async move {
-            Ok::<_, Infallible>(service_fn(move |req| {
+            Ok::<_, Infallible>(service_fn(move |mut req| {
                 let app = app.clone();
+                req.extensions_mut().insert(remote_addr);
                 app.handle(req)
             }))
         }
@@ -1,10 +1,19 @@
 use std::collections::HashSet;
+use std::ops::Bound;
 
 use crate::entities::bucket::Bucket;
+use crate::entities::error::Error;
 use crate::entities::object::Object;
+use crate::entities::upload::{Part, Upload};
 use crate::entities::user::User;
 
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use serde_json::json;
+use sled::transaction::{TransactionError, Transactional};
+
+const LEGACY_OBJECTS_TREE: &str = "bucket_name_to_objects";
+const KEY_LAYOUT: &str = "key_layout";
 
 #[derive(Clone)]
 pub struct Db {
@@ -13,109 +22,432 @@ pub struct Db {
     user_id_to_user: sled::Tree,
     user_id_to_bucket: sled::Tree,
     bucket_name_to_bucket: sled::Tree,
-    bucket_name_to_objects: sled::Tree,
+    bucket_key_to_object: sled::Tree,
+    bucket_key_to_versions: sled::Tree,
+    upload_id_to_upload: sled::Tree,
+    upload_id_to_parts: sled::Tree,
 }
 
 impl Db {
-    pub fn new(path: &str) -> Self {
-        let db = sled::open(path).unwrap();
+    pub fn new(path: &str) -> Result<Self, Error> {
+        let db = sled::open(path)?;
         Self {
-            access_key_to_user_id: db.open_tree("access_key_to_user_id").unwrap(),
-            user_id_to_user: db.open_tree("user_id_to_user").unwrap(),
-            user_id_to_bucket: db.open_tree("user_id_to_bucket").unwrap(),
-            bucket_name_to_bucket: db.open_tree("bucket_name_to_bucket").unwrap(),
-            bucket_name_to_objects: db.open_tree("bucket_name_to_objects").unwrap(),
-            db: db,
+            access_key_to_user_id: db.open_tree("access_key_to_user_id")?,
+            user_id_to_user: db.open_tree("user_id_to_user")?,
+            user_id_to_bucket: db.open_tree("user_id_to_bucket")?,
+            bucket_name_to_bucket: db.open_tree("bucket_name_to_bucket")?,
+            bucket_key_to_object: db.open_tree("bucket_key_to_object")?,
+            bucket_key_to_versions: db.open_tree("bucket_key_to_versions")?,
+            upload_id_to_upload: db.open_tree("upload_id_to_upload")?,
+            upload_id_to_parts: db.open_tree("upload_id_to_parts")?,
+            db,
+        }
+        .migrate()
+    }
+
+    // databases created before objects were indexed one per key keep a JSON
+    // array of objects per bucket; move them over once and drop the old tree
+    fn migrate(self) -> Result<Self, Error> {
+        if !self
+            .db
+            .tree_names()
+            .iter()
+            .any(|name| name == LEGACY_OBJECTS_TREE.as_bytes())
+        {
+            return Ok(self);
+        }
+
+        let legacy = self.db.open_tree(LEGACY_OBJECTS_TREE)?;
+        let mut batch = sled::Batch::default();
+        for entry in legacy.iter() {
+            let (_, objects_buf) = entry?;
+            let objects: Vec<Object> = serde_json::from_slice(&objects_buf)?;
+            for object in objects {
+                batch.insert(
+                    object_key(&object.bucket, &object.key),
+                    serde_json::to_vec(&object)?,
+                );
+            }
+        }
+        self.bucket_key_to_object.apply_batch(batch)?;
+        self.bucket_key_to_object.flush()?;
+        self.db.drop_tree(LEGACY_OBJECTS_TREE)?;
+
+        Ok(self)
+    }
+
+    // the version of the layout object files are stored in, 0 before it was
+    // first recorded
+    pub fn get_key_layout(&self) -> Result<u64, Error> {
+        match self.db.get(KEY_LAYOUT)? {
+            Some(layout_buf) => Ok(serde_json::from_slice(&layout_buf)?),
+            None => Ok(0),
+        }
+    }
+
+    pub fn set_key_layout(&self, layout: u64) -> Result<(), Error> {
+        self.db.insert(KEY_LAYOUT, serde_json::to_vec(&layout)?)?;
+        Ok(())
+    }
+
+    pub fn generate_id(&self) -> Result<u64, Error> {
+        Ok(self.db.generate_id()?)
+    }
+
+    pub fn get_user_by_access_key(&self, access_key: &str) -> Result<Option<User>, Error> {
+        let user_id = match self.access_key_to_user_id.get(access_key)? {
+            Some(user_id) => user_id,
+            None => return Ok(None),
+        };
+        match self.user_id_to_user.get(user_id)? {
+            Some(user_buf) => Ok(Some(serde_json::from_slice(&user_buf)?)),
+            None => Ok(None),
         }
     }
 
-    pub fn get_user_by_access_key(&self, access_key: &str) -> Option<User> {
-        let user_id = self.access_key_to_user_id.get(access_key).unwrap()?;
-        let user_buf = self.user_id_to_user.get(user_id).unwrap()?;
-        let user: User = serde_json::from_slice(&user_buf).unwrap();
-        Some(user)
+    pub fn get_user(&self, user_id: &str) -> Result<Option<User>, Error> {
+        match self.user_id_to_user.get(user_id)? {
+            Some(user_buf) => Ok(Some(serde_json::from_slice(&user_buf)?)),
+            None => Ok(None),
+        }
     }
 
-    pub fn create_user(&self, user: &User) {
-        if self.user_id_to_user.get(&user.id).unwrap().is_some() {
-            panic!("user already exists!");
+    pub fn create_user(&self, user: &User) -> Result<(), Error> {
+        if self.user_id_to_user.get(&user.id)?.is_some() {
+            return Err(Error::InvalidArgument(format!(
+                "user {} already exists",
+                user.id
+            )));
         }
 
         self.user_id_to_user
-            .insert(&user.id, serde_json::to_vec(&user).unwrap())
-            .unwrap();
+            .insert(&user.id, serde_json::to_vec(&user)?)?;
         self.user_id_to_bucket
-            .insert(&user.id, serde_json::to_vec(&json!([])).unwrap())
-            .unwrap();
+            .insert(&user.id, serde_json::to_vec(&json!([]))?)?;
         self.access_key_to_user_id
-            .insert(&user.access_key, &user.id[..])
-            .unwrap();
+            .insert(&user.access_key, &user.id[..])?;
+        Ok(())
     }
 
-    pub fn create_bucket(&self, bucket: &Bucket) {
+    pub fn create_bucket(&self, bucket: &Bucket) -> Result<(), Error> {
         if self
             .bucket_name_to_bucket
-            .get(&bucket.name)
-            .unwrap()
-            .is_some()
+            .compare_and_swap(
+                &bucket.name,
+                None as Option<&[u8]>,
+                Some(serde_json::to_vec(bucket)?),
+            )?
+            .is_err()
         {
-            panic!("bucket already exists!")
+            return Err(Error::BucketAlreadyExists);
         }
 
+        update_json(
+            &self.user_id_to_bucket,
+            &bucket.owner_id,
+            |user_buckets: &mut HashSet<Bucket>| {
+                user_buckets.insert(bucket.to_owned());
+            },
+        )
+    }
+
+    pub fn update_bucket<F>(&self, bucket_name: &str, mut f: F) -> Result<Bucket, Error>
+    where
+        F: FnMut(&mut Bucket),
+    {
+        loop {
+            let old = self
+                .bucket_name_to_bucket
+                .get(bucket_name)?
+                .ok_or(Error::NoSuchBucket)?;
+            let mut bucket = serde_json::from_slice(&old)?;
+            f(&mut bucket);
+
+            if self
+                .bucket_name_to_bucket
+                .compare_and_swap(bucket_name, Some(old), Some(serde_json::to_vec(&bucket)?))?
+                .is_ok()
+            {
+                return Ok(bucket);
+            }
+        }
+    }
+
+    pub fn get_bucket(&self, bucket_name: &str) -> Result<Option<Bucket>, Error> {
+        match self.bucket_name_to_bucket.get(bucket_name)? {
+            Some(bucket_buf) => Ok(Some(serde_json::from_slice(&bucket_buf)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_buckets(&self) -> impl Iterator<Item = Result<Bucket, Error>> {
         self.bucket_name_to_bucket
-            .insert(&bucket.name, serde_json::to_vec(bucket).unwrap())
-            .unwrap();
+            .iter()
+            .map(|entry| Ok(serde_json::from_slice(&entry?.1)?))
+    }
+
+    pub fn get_buckets_by_user_id(&self, user_id: &str) -> Result<HashSet<Bucket>, Error> {
         let user_buckets_buf = self
             .user_id_to_bucket
-            .get(&bucket.owner_id)
-            .unwrap()
-            .unwrap();
-        let mut user_buckets: Vec<Bucket> = serde_json::from_slice(&user_buckets_buf).unwrap();
-        user_buckets.push(bucket.to_owned());
-        self.user_id_to_bucket
-            .insert(&bucket.owner_id, serde_json::to_vec(&user_buckets).unwrap())
-            .unwrap();
+            .get(user_id)?
+            .unwrap_or_else(|| sled::IVec::from("[]"));
+        Ok(serde_json::from_slice(&user_buckets_buf)?)
     }
 
-    pub fn get_buckets_by_user_id(&self, user_id: &str) -> HashSet<Bucket> {
-        let user_buckets_buf = self.user_id_to_bucket.get(user_id).unwrap().unwrap();
-        serde_json::from_slice(&user_buckets_buf).unwrap()
+    pub fn has_objects(&self, bucket_name: &str) -> Result<bool, Error> {
+        for tree in [&self.bucket_key_to_object, &self.bucket_key_to_versions] {
+            if let Some(entry) = tree.scan_prefix(object_key(bucket_name, "")).next() {
+                entry?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
     }
 
-    pub fn get_objects_by_bucket_name(&self, bucket_name: &str) -> HashSet<Object> {
-        let objects_buf = self
-            .bucket_name_to_objects
-            .get(bucket_name)
-            .unwrap()
-            .unwrap_or(sled::IVec::from("[]"));
-        serde_json::from_slice(&objects_buf).unwrap()
+    pub fn get_objects_by_prefix<'a>(
+        &'a self,
+        bucket_name: &str,
+        prefix: &str,
+        start_after: Option<&str>,
+    ) -> impl Iterator<Item = Result<Object, Error>> + 'a {
+        let scan_prefix = object_key(bucket_name, prefix);
+        let range = match start_after.map(|s| object_key(bucket_name, s)) {
+            Some(start) if start >= scan_prefix => self
+                .bucket_key_to_object
+                .range::<Vec<u8>, _>((Bound::Excluded(start), Bound::Unbounded)),
+            _ => self.bucket_key_to_object.range(scan_prefix.clone()..),
+        };
+
+        range
+            .take_while(move |entry| {
+                entry
+                    .as_ref()
+                    .map_or(true, |(key, _)| key.starts_with(&scan_prefix))
+            })
+            .map(|entry| Ok(serde_json::from_slice(&entry?.1)?))
     }
 
-    pub fn create_object(&self, object: &Object) {
-        let mut objects = self.get_objects_by_bucket_name(&object.bucket);
-        objects.insert(object.to_owned());
+    pub fn create_object(&self, object: &Object) -> Result<(), Error> {
+        self.bucket_key_to_object.insert(
+            object_key(&object.bucket, &object.key),
+            serde_json::to_vec(object)?,
+        )?;
+        Ok(())
+    }
 
-        self.bucket_name_to_objects
-            .insert(&object.bucket, serde_json::to_vec(&objects).unwrap())
-            .unwrap();
+    pub fn get_object(&self, bucket: &str, object: &str) -> Result<Option<Object>, Error> {
+        match self.bucket_key_to_object.get(object_key(bucket, object))? {
+            Some(object_buf) => Ok(Some(serde_json::from_slice(&object_buf)?)),
+            None => Ok(None),
+        }
     }
 
-    pub fn get_object(&self, bucket: &str, object: &str) -> Option<Object> {
-        let objects = self.get_objects_by_bucket_name(bucket);
+    pub fn update_object<F>(&self, bucket: &str, object: &str, mut f: F) -> Result<Object, Error>
+    where
+        F: FnMut(&mut Object),
+    {
+        let key = object_key(bucket, object);
+        loop {
+            let old = self
+                .bucket_key_to_object
+                .get(&key)?
+                .ok_or(Error::NoSuchKey)?;
+            let mut object = serde_json::from_slice(&old)?;
+            f(&mut object);
 
-        objects.into_iter().find(|o| o.key == object)
+            if self
+                .bucket_key_to_object
+                .compare_and_swap(&key, Some(old), Some(serde_json::to_vec(&object)?))?
+                .is_ok()
+            {
+                return Ok(object);
+            }
+        }
+    }
+
+    // noncurrent versions and delete markers of a key, newest first
+    pub fn get_object_versions(&self, bucket: &str, object: &str) -> Result<Vec<Object>, Error> {
+        self.bucket_key_to_versions
+            .scan_prefix(version_prefix(bucket, object))
+            .map(|entry| Ok(serde_json::from_slice(&entry?.1)?))
+            .collect()
     }
 
-    pub fn delete_bucket(&self, bucket: &str) {
-        self.bucket_name_to_bucket.remove(bucket).unwrap();
-        self.bucket_name_to_objects.remove(bucket).unwrap();
+    pub fn get_object_versions_by_prefix<'a>(
+        &'a self,
+        bucket_name: &str,
+        prefix: &str,
+    ) -> impl Iterator<Item = Result<Object, Error>> + 'a {
+        self.bucket_key_to_versions
+            .scan_prefix(object_key(bucket_name, prefix))
+            .map(|entry| Ok(serde_json::from_slice(&entry?.1)?))
     }
 
-    pub fn delete_object(&self, bucket: &str, object: &str) {
-        let mut objects = self.get_objects_by_bucket_name(bucket);
-        objects.retain(|o| o.key == object);
-        self.bucket_name_to_objects
-            .insert(bucket, serde_json::to_vec(&objects).unwrap())
-            .unwrap();
+    // versions are keyed by an inverted sequence number so a key's versions
+    // scan newest first
+    pub fn create_object_version(&self, object: &Object) -> Result<(), Error> {
+        let sequence = u64::MAX - self.db.generate_id()?;
+        self.bucket_key_to_versions.insert(
+            [
+                version_prefix(&object.bucket, &object.key),
+                sequence.to_be_bytes().to_vec(),
+            ]
+            .concat(),
+            serde_json::to_vec(object)?,
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_object_version(
+        &self,
+        bucket: &str,
+        object: &str,
+        version_id: Option<&str>,
+    ) -> Result<Option<Object>, Error> {
+        for entry in self
+            .bucket_key_to_versions
+            .scan_prefix(version_prefix(bucket, object))
+        {
+            let (key, object_buf) = entry?;
+            let version: Object = serde_json::from_slice(&object_buf)?;
+            if version.version_id.as_deref() == version_id {
+                self.bucket_key_to_versions.remove(key)?;
+                return Ok(Some(version));
+            }
+        }
+        Ok(None)
+    }
+
+    // uploads still in progress go with the bucket, so one recreated under
+    // the same name doesn't inherit them
+    pub fn delete_bucket(&self, bucket: &Bucket) -> Result<(), Error> {
+        self.bucket_name_to_bucket.remove(&bucket.name)?;
+
+        for tree in [&self.bucket_key_to_object, &self.bucket_key_to_versions] {
+            let mut batch = sled::Batch::default();
+            for entry in tree.scan_prefix(object_key(&bucket.name, "")) {
+                batch.remove(entry?.0);
+            }
+            tree.apply_batch(batch)?;
+        }
+
+        let mut upload_ids = Vec::new();
+        for upload in self.get_uploads() {
+            let upload = upload?;
+            if upload.bucket == bucket.name {
+                upload_ids.push(upload.id);
+            }
+        }
+        (&self.upload_id_to_upload, &self.upload_id_to_parts)
+            .transaction(|(uploads, parts)| {
+                for id in &upload_ids {
+                    uploads.remove(id.as_bytes())?;
+                    parts.remove(id.as_bytes())?;
+                }
+                Ok(())
+            })
+            .map_err(|e: TransactionError| Error::InternalError(e.to_string()))?;
+
+        update_json(
+            &self.user_id_to_bucket,
+            &bucket.owner_id,
+            |user_buckets: &mut HashSet<Bucket>| {
+                user_buckets.remove(bucket);
+            },
+        )
+    }
+
+    pub fn delete_object(&self, bucket: &str, object: &str) -> Result<(), Error> {
+        self.bucket_key_to_object
+            .remove(object_key(bucket, object))?;
+        Ok(())
+    }
+
+    pub fn delete_objects(&self, bucket: &str, objects: &[&str]) -> Result<(), Error> {
+        let mut batch = sled::Batch::default();
+        for object in objects {
+            batch.remove(object_key(bucket, object));
+        }
+        self.bucket_key_to_object.apply_batch(batch)?;
+        Ok(())
+    }
+
+    pub fn create_upload(&self, upload: &Upload) -> Result<(), Error> {
+        self.upload_id_to_upload
+            .insert(&upload.id, serde_json::to_vec(upload)?)?;
+        self.upload_id_to_parts
+            .insert(&upload.id, serde_json::to_vec(&json!([]))?)?;
+        Ok(())
+    }
+
+    pub fn get_upload(&self, upload_id: &str) -> Result<Option<Upload>, Error> {
+        match self.upload_id_to_upload.get(upload_id)? {
+            Some(upload_buf) => Ok(Some(serde_json::from_slice(&upload_buf)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_uploads(&self) -> impl Iterator<Item = Result<Upload, Error>> {
+        self.upload_id_to_upload
+            .iter()
+            .map(|entry| Ok(serde_json::from_slice(&entry?.1)?))
+    }
+
+    pub fn get_parts_by_upload_id(&self, upload_id: &str) -> Result<Vec<Part>, Error> {
+        let parts_buf = self
+            .upload_id_to_parts
+            .get(upload_id)?
+            .unwrap_or_else(|| sled::IVec::from("[]"));
+        Ok(serde_json::from_slice(&parts_buf)?)
+    }
+
+    pub fn create_part(&self, upload_id: &str, part: &Part) -> Result<(), Error> {
+        update_json(
+            &self.upload_id_to_parts,
+            upload_id,
+            |parts: &mut Vec<Part>| {
+                parts.retain(|p| p.number != part.number);
+                parts.push(part.to_owned());
+                parts.sort_by_key(|p| p.number);
+            },
+        )
+    }
+
+    pub fn delete_upload(&self, upload_id: &str) -> Result<(), Error> {
+        self.upload_id_to_upload.remove(upload_id)?;
+        self.upload_id_to_parts.remove(upload_id)?;
+        Ok(())
+    }
+}
+
+fn object_key(bucket: &str, key: &str) -> Vec<u8> {
+    [bucket.as_bytes(), b"\0", key.as_bytes()].concat()
+}
+
+fn version_prefix(bucket: &str, key: &str) -> Vec<u8> {
+    [object_key(bucket, key), b"\0".to_vec()].concat()
+}
+
+// read-modify-write of a JSON value that retries until no concurrent writer
+// changed it in between
+fn update_json<T, F>(tree: &sled::Tree, key: &str, mut f: F) -> Result<(), Error>
+where
+    T: DeserializeOwned + Serialize + Default,
+    F: FnMut(&mut T),
+{
+    loop {
+        let old = tree.get(key)?;
+        let mut value = match &old {
+            Some(buf) => serde_json::from_slice(buf)?,
+            None => T::default(),
+        };
+        f(&mut value);
+
+        if tree
+            .compare_and_swap(key, old, Some(serde_json::to_vec(&value)?))?
+            .is_ok()
+        {
+            return Ok(());
+        }
     }
 }
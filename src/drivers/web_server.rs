@@ -1,29 +1,68 @@
 use std::clone::Clone;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::Infallible;
+use std::net::SocketAddr;
 use std::sync::Arc;
 
-use chrono::Utc;
+use chrono::{DateTime, Local, Utc};
 use futures::TryStreamExt;
-use hmac::Mac;
 use hyper::header::HeaderValue;
+use hyper::http::response;
 use hyper::{Body, Method, Request, Response, StatusCode};
 use md5::{Digest, Md5};
-use tokio::sync::Mutex;
+use regex::Regex;
 
-use crate::adapters::bucket::ListAllMyBucketsResult;
-use crate::adapters::object::ListBucketResult;
+use crate::adapters::acl::{AccessControlPolicy, GrantResult};
+use crate::adapters::bucket::{
+    CreateBucketConfiguration, ListAllMyBucketsResult, LocationConstraint, VersioningConfiguration,
+};
+use crate::adapters::error::ErrorResult;
+use crate::adapters::lifecycle::LifecycleConfiguration;
+use crate::adapters::object::{
+    CommonPrefixResult, CopyObjectResult, Delete, DeleteErrorResult, DeleteResult, DeletedResult,
+    ListBucketResult, ListBucketResultV2, ListVersionsResult, VersionResult,
+};
+use crate::adapters::policy::PolicyDocument;
+use crate::adapters::upload::{
+    CompleteMultipartUpload, CompleteMultipartUploadResult, CopyPartResult,
+    InitiateMultipartUploadResult,
+};
 use crate::adapters::user::OwnerResult;
-use crate::drivers::s3::{Auth, Operation};
-use crate::entities::object::Object;
+use crate::drivers::s3::{
+    base64_encode, hex_decode, hex_encode, parse_query, url_decode, Auth, CopySourceParams,
+    ListObjectsParams, Operation, PayloadStream,
+};
+use crate::entities::acl::{Acl, CannedAcl, Grant, Grantee};
+use crate::entities::bucket::Bucket;
+use crate::entities::error::Error;
+use crate::entities::object::{Metadata, Object};
+use crate::entities::upload::{Part, Upload};
 use crate::entities::user::User;
-use crate::interactors::storage::Storage;
+use crate::interactors::authorization::{authorize, validate_policy, Action};
+use crate::interactors::conditions::Conditions;
+use crate::interactors::storage::{CopySource, ObjectOptions, ObjectRange, ObjectStream, Storage};
 
 #[derive(Clone)]
 pub struct App {
-    pub storage: Arc<Mutex<Storage>>,
+    pub storage: Arc<Storage>,
 }
 
 const AUTH_HEADER: &str = "Authorization";
+const ACL_HEADER: &str = "x-amz-acl";
+const TAGGING_HEADER: &str = "x-amz-tagging";
+const META_HEADER_PREFIX: &str = "x-amz-meta-";
+const COPY_SOURCE_HEADER: &str = "x-amz-copy-source";
+const MAX_USER_METADATA: usize = 2 * 1024;
+const RESPONSE_OVERRIDES: [(&str, &str); 6] = [
+    ("response-content-type", "Content-Type"),
+    ("response-content-language", "Content-Language"),
+    ("response-expires", "Expires"),
+    ("response-cache-control", "Cache-Control"),
+    ("response-content-disposition", "Content-Disposition"),
+    ("response-content-encoding", "Content-Encoding"),
+];
+const MAX_KEYS: usize = 1000;
+const DEFAULT_CONTENT_TYPE: &str = "binary/octet-stream";
 
 impl App {
     fn get_auth_header(&self, req: &Request<Body>) -> String {
@@ -35,20 +74,321 @@ impl App {
             .to_string()
     }
 
-    fn check_signature(&self, user: &User, auth: &Auth, req: &Request<Body>) -> bool {
-        let string_to_sign = auth.string_to_sign(&req);
-        let mut key = auth.key_builder(&user.secret_access_key);
-        key.update(string_to_sign.as_bytes());
+    fn get_query_params(&self, req: &Request<Body>) -> HashMap<String, String> {
+        parse_query(req.uri().query().unwrap_or(""))
+    }
+
+    fn get_canned_acl(&self, req: &Request<Body>) -> Result<Option<CannedAcl>, Error> {
+        req.headers()
+            .get(ACL_HEADER)
+            .map(|acl| CannedAcl::parse(acl.to_str().unwrap_or("")))
+            .transpose()
+    }
+
+    fn get_copy_source_params(&self, req: &Request<Body>) -> CopySourceParams {
+        CopySourceParams::parse(
+            req.headers()
+                .get(COPY_SOURCE_HEADER)
+                .and_then(|source| source.to_str().ok())
+                .unwrap_or(""),
+        )
+    }
+
+    fn get_content_md5(&self, req: &Request<Body>) -> Option<String> {
+        req.headers()
+            .get("Content-MD5")
+            .map(|md5| md5.to_str().unwrap_or("").trim().to_string())
+    }
+
+    // only a single range is supported; like a malformed header, anything
+    // else is ignored and the whole object is returned
+    fn get_range(&self, req: &Request<Body>) -> Result<Option<ObjectRange>, Error> {
+        let part_number = self.get_query_params(req).get("partNumber").cloned();
+        let header = req.headers().get("Range").and_then(|r| r.to_str().ok());
+
+        let range_re = Regex::new(r"^bytes=(\d*)-(\d*)$").unwrap();
+        let range = header
+            .and_then(|range| range_re.captures(range.trim()))
+            .and_then(|range| {
+                let start = range[1].parse().ok();
+                let end = range[2].parse().ok();
+                match (start, end) {
+                    (Some(start), Some(end)) if start <= end => {
+                        Some(ObjectRange::Between(start, end))
+                    }
+                    (Some(start), None) if range[2].is_empty() => Some(ObjectRange::From(start)),
+                    (None, Some(length)) if range[1].is_empty() => {
+                        Some(ObjectRange::Suffix(length))
+                    }
+                    _ => None,
+                }
+            });
+
+        match (part_number, range) {
+            (Some(_), Some(_)) => Err(Error::InvalidArgument(
+                "Cannot specify both Range header and partNumber query parameter.".to_string(),
+            )),
+            (Some(part_number), None) => Ok(Some(ObjectRange::Part(
+                part_number.parse().map_err(|_| Error::InvalidPartNumber)?,
+            ))),
+            (None, range) => Ok(range),
+        }
+    }
+
+    fn get_conditions(&self, req: &Request<Body>) -> Conditions {
+        Conditions {
+            content_md5: self.get_content_md5(req),
+            ..self.get_preconditions(req, "")
+        }
+    }
+
+    // copies send the conditions on their source with a prefix, e.g.
+    // x-amz-copy-source-if-match
+    fn get_preconditions(&self, req: &Request<Body>, prefix: &str) -> Conditions {
+        let header = |name: &str| {
+            req.headers()
+                .get(format!("{}{}", prefix, name))
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string())
+        };
+        // invalid dates are ignored, as HTTP requires
+        let date = |name: &str| {
+            header(name)
+                .and_then(|date| DateTime::parse_from_rfc2822(&date).ok())
+                .map(|date| date.with_timezone(&Utc))
+        };
+
+        Conditions {
+            content_md5: None,
+            if_match: header("If-Match"),
+            if_none_match: header("If-None-Match"),
+            if_modified_since: date("If-Modified-Since"),
+            if_unmodified_since: date("If-Unmodified-Since"),
+        }
+    }
+
+    fn get_copy_source(
+        &self,
+        req: &Request<Body>,
+        params: &CopySourceParams,
+    ) -> Result<CopySource, Error> {
+        if params.bucket.is_empty() || params.key.is_empty() {
+            return Err(Error::InvalidArgument(
+                "Copy Source must mention the source bucket and key: sourcebucket/sourcekey."
+                    .to_string(),
+            ));
+        }
+        let directive = |name: &str| match req.headers().get(name).map(|d| d.to_str()) {
+            None => Ok(true),
+            Some(Ok("COPY")) => Ok(true),
+            Some(Ok("REPLACE")) => Ok(false),
+            Some(_) => Err(Error::InvalidArgument(format!(
+                "Unknown {} header value.",
+                name
+            ))),
+        };
+
+        Ok(CopySource {
+            bucket: params.bucket.to_string(),
+            key: params.key.to_string(),
+            version_id: params.version_id.clone(),
+            copy_metadata: directive("x-amz-metadata-directive")?,
+            copy_tags: directive("x-amz-tagging-directive")?,
+            conditions: self.get_preconditions(req, "x-amz-copy-source-"),
+        })
+    }
+
+    // unlike Range, a copy range must name both of its ends
+    fn get_copy_range(&self, req: &Request<Body>) -> Result<Option<ObjectRange>, Error> {
+        let header = match req.headers().get("x-amz-copy-source-range") {
+            Some(header) => header.to_str().unwrap_or(""),
+            None => return Ok(None),
+        };
+
+        let range_re = Regex::new(r"^bytes=(\d+)-(\d+)$").unwrap();
+        range_re
+            .captures(header.trim())
+            .and_then(|range| Some((range[1].parse().ok()?, range[2].parse().ok()?)))
+            .filter(|(start, end)| start <= end)
+            .map(|(start, end)| Some(ObjectRange::Between(start, end)))
+            .ok_or_else(|| {
+                Error::InvalidArgument(
+                    "The x-amz-copy-source-range value must be of the form bytes=first-last where first and last are the zero-based offsets of the first and last bytes to copy".to_string(),
+                )
+            })
+    }
+
+    // tags are sent URL encoded, like a query string
+    fn get_tags(&self, req: &Request<Body>) -> BTreeMap<String, String> {
+        req.headers()
+            .get(TAGGING_HEADER)
+            .map(|tags| {
+                parse_query(tags.to_str().unwrap_or(""))
+                    .into_iter()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn get_metadata(&self, req: &Request<Body>) -> Result<Metadata, Error> {
+        let header = |name: &str| {
+            req.headers()
+                .get(name)
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).to_string())
+        };
+
+        let user: BTreeMap<String, String> = req
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| {
+                name.as_str().strip_prefix(META_HEADER_PREFIX).map(|name| {
+                    (
+                        name.to_string(),
+                        String::from_utf8_lossy(value.as_bytes()).to_string(),
+                    )
+                })
+            })
+            .collect();
+        if user.iter().map(|(k, v)| k.len() + v.len()).sum::<usize>() > MAX_USER_METADATA {
+            return Err(Error::MetadataTooLarge);
+        }
+
+        Ok(Metadata {
+            content_type: header("Content-Type"),
+            // aws-chunked only describes how the payload was sent
+            content_encoding: header("Content-Encoding")
+                .map(|encoding| {
+                    encoding
+                        .split(',')
+                        .map(|e| e.trim())
+                        .filter(|e| !e.is_empty() && *e != "aws-chunked")
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                })
+                .filter(|encoding| !encoding.is_empty()),
+            content_disposition: header("Content-Disposition"),
+            cache_control: header("Cache-Control"),
+            user,
+        })
+    }
+
+    fn get_list_objects_params(&self, query: &HashMap<String, String>) -> ListObjectsParams {
+        ListObjectsParams {
+            prefix: query.get("prefix").cloned().unwrap_or_default(),
+            delimiter: query.get("delimiter").cloned(),
+            max_keys: query
+                .get("max-keys")
+                .and_then(|m| m.parse().ok())
+                .map_or(MAX_KEYS, |m: usize| m.min(MAX_KEYS)),
+            marker: None,
+            version_id_marker: None,
+            continuation_token: None,
+        }
+    }
+
+    fn check_signature(&self, user: &User, auth: &Auth, req: &Request<Body>) -> Result<(), Error> {
+        if auth.is_not_yet_valid(Utc::now()) {
+            return Err(Error::RequestNotYetValid);
+        }
+        if auth.is_expired(Utc::now()) {
+            return Err(Error::AccessDenied);
+        }
+        if auth.is_skewed(req, Utc::now())? {
+            return Err(Error::RequestTimeTooSkewed);
+        }
+        if !auth.verify(&user.secret_access_key, req)? {
+            return Err(Error::SignatureDoesNotMatch);
+        }
+        Ok(())
+    }
+
+    fn get_policy_context(
+        &self,
+        req: &Request<Body>,
+        operation: &Operation,
+    ) -> HashMap<String, String> {
+        let mut context = HashMap::new();
+        if let Some(addr) = req.extensions().get::<SocketAddr>() {
+            context.insert("aws:sourceip".to_string(), addr.ip().to_string());
+        }
+        if let Operation::ListObjects(_, params) | Operation::ListObjectsV2(_, params) = operation {
+            context.insert("s3:prefix".to_string(), params.prefix.to_string());
+            context.insert("s3:max-keys".to_string(), params.max_keys.to_string());
+            if let Some(delimiter) = &params.delimiter {
+                context.insert("s3:delimiter".to_string(), delimiter.to_string());
+            }
+        }
+        context
+    }
+
+    async fn authorize(
+        &self,
+        user: &User,
+        operation: &Operation,
+        context: &HashMap<String, String>,
+    ) -> Result<(), Error> {
+        if let Operation::DeleteObjects(bucket) = operation {
+            self.find_bucket(bucket).await?;
+            return Ok(());
+        }
 
-        format!("{:x}", key.finalize().into_bytes()) == auth.signature
+        let (action, bucket, key) = operation.action();
+        self.authorize_action(user, action, bucket, key, operation.version_id(), context)
+            .await?;
+
+        match operation.copy_source() {
+            Some((action, bucket, key, version_id)) => {
+                self.authorize_action(user, action, Some(bucket), Some(key), version_id, context)
+                    .await
+            }
+            None => Ok(()),
+        }
     }
 
-    async fn list_buckets(&self, user: &User) -> Result<ListAllMyBucketsResult, String> {
-        let storage = self.storage.lock().await;
+    async fn authorize_action(
+        &self,
+        user: &User,
+        action: Action,
+        bucket: Option<&str>,
+        key: Option<&str>,
+        version_id: Option<&str>,
+        context: &HashMap<String, String>,
+    ) -> Result<(), Error> {
+        let bucket = match bucket {
+            Some(bucket) => Some(self.find_bucket(bucket).await?),
+            None => None,
+        };
+        let object = match (&bucket, key) {
+            (Some(bucket), Some(key)) => {
+                match self.head_object(&bucket.name, key, version_id).await {
+                    Ok(object) => Some(object),
+                    Err(Error::NoSuchKey | Error::NoSuchVersion | Error::MethodNotAllowed) => None,
+                    Err(e) => return Err(e),
+                }
+            }
+            _ => None,
+        };
+
+        authorize(user, action, bucket.as_ref(), object.as_ref(), key, context)
+    }
+
+    fn payload(
+        &self,
+        auth: Option<&Auth>,
+        user: &User,
+        req: Request<Body>,
+    ) -> Result<PayloadStream, Error> {
+        match auth {
+            Some(auth) => auth.payload(&user.secret_access_key, req),
+            None => Ok(Box::pin(req.into_body().map_err(Error::from))),
+        }
+    }
 
+    async fn list_buckets(&self, user: &User) -> Result<ListAllMyBucketsResult, Error> {
         Ok(ListAllMyBucketsResult {
-            buckets: storage
-                .list_buckets(&user.id)
+            buckets: self
+                .storage
+                .list_buckets(&user.id)?
                 .iter()
                 .map(|b| b.into())
                 .collect(),
@@ -59,28 +399,343 @@ impl App {
         })
     }
 
-    async fn create_bucket(&self, user: &User, bucket: &str) -> Result<(), String> {
-        let mut storage = self.storage.lock().await;
-        Ok(storage.create_bucket(&user.id, bucket))
+    async fn create_bucket(
+        &self,
+        user: &User,
+        bucket: &str,
+        acl: Option<CannedAcl>,
+        body: &[u8],
+    ) -> Result<(), Error> {
+        // the configuration body is optional
+        let location = match body.is_empty() {
+            true => None,
+            false => {
+                CreateBucketConfiguration::from_xml(&String::from_utf8_lossy(body))
+                    .ok_or(Error::MalformedXML)?
+                    .location
+            }
+        };
+        let location_re = Regex::new(r"^[a-z0-9]+(-[a-z0-9]+)*$").unwrap();
+        if location.as_ref().is_some_and(|l| !location_re.is_match(l)) {
+            return Err(Error::InvalidLocationConstraint);
+        }
+
+        self.storage
+            .create_bucket(&user.id, bucket, location.as_deref())
+            .await?;
+
+        match acl {
+            Some(acl) => self
+                .storage
+                .set_bucket_acl(bucket, Acl::canned(acl, &user.id, &user.id))
+                .map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
+    async fn get_bucket_acl(&self, bucket: &str) -> Result<AccessControlPolicy, Error> {
+        let bucket = self.find_bucket(bucket).await?;
+
+        Ok(self.access_control_policy(&bucket.owner_id, &bucket.acl))
+    }
+
+    async fn put_bucket_acl(
+        &self,
+        bucket: &str,
+        acl: Option<CannedAcl>,
+        body: &[u8],
+    ) -> Result<(), Error> {
+        let owner_id = self.find_bucket(bucket).await?.owner_id;
+        let acl = self.request_acl(acl, body, &owner_id, &owner_id)?;
+
+        self.storage.set_bucket_acl(bucket, acl).map(|_| ())
+    }
+
+    async fn get_object_acl(&self, bucket: &str, key: &str) -> Result<AccessControlPolicy, Error> {
+        let object = self.head_object(bucket, key, None).await?;
+
+        Ok(self.access_control_policy(&object.owner_id, &object.acl))
+    }
+
+    async fn put_object_acl(
+        &self,
+        bucket: &str,
+        key: &str,
+        acl: Option<CannedAcl>,
+        body: &[u8],
+    ) -> Result<(), Error> {
+        let bucket_owner_id = self.find_bucket(bucket).await?.owner_id;
+        let owner_id = self.head_object(bucket, key, None).await?.owner_id;
+        let acl = self.request_acl(acl, body, &owner_id, &bucket_owner_id)?;
+
+        self.storage.set_object_acl(bucket, key, acl).map(|_| ())
+    }
+
+    async fn object_options(
+        &self,
+        user: &User,
+        bucket: &str,
+        req: &Request<Body>,
+    ) -> Result<ObjectOptions, Error> {
+        let bucket_owner_id = self.find_bucket(bucket).await?.owner_id;
+
+        Ok(ObjectOptions {
+            acl: Acl::canned(
+                self.get_canned_acl(req)?.unwrap_or(CannedAcl::Private),
+                &user.id,
+                &bucket_owner_id,
+            ),
+            tags: self.get_tags(req),
+            metadata: self.get_metadata(req)?,
+        })
+    }
+
+    // an ACL is set either with a canned ACL header or an AccessControlPolicy
+    // body, never both
+    fn request_acl(
+        &self,
+        acl: Option<CannedAcl>,
+        body: &[u8],
+        owner_id: &str,
+        bucket_owner_id: &str,
+    ) -> Result<Acl, Error> {
+        if let Some(acl) = acl {
+            if !body.is_empty() {
+                return Err(Error::InvalidArgument(format!(
+                    "The {} header can't be combined with an AccessControlPolicy body.",
+                    ACL_HEADER
+                )));
+            }
+            return Ok(Acl::canned(acl, owner_id, bucket_owner_id));
+        }
+
+        let policy = AccessControlPolicy::from_xml(&String::from_utf8_lossy(body))
+            .ok_or(Error::MalformedACLError)?;
+        for grant in &policy.grants {
+            if let Grantee::User(id) = &grant.grantee {
+                self.storage.find_user_by_id(id)?;
+            }
+        }
+
+        Ok(Acl {
+            grants: policy
+                .grants
+                .into_iter()
+                .map(|grant| Grant {
+                    grantee: grant.grantee,
+                    permission: grant.permission,
+                })
+                .collect(),
+        })
+    }
+
+    fn access_control_policy(&self, owner_id: &str, acl: &Acl) -> AccessControlPolicy {
+        let display_name = |id: &str| {
+            self.storage
+                .find_user_by_id(id)
+                .ok()
+                .map(|user| user.display_name)
+        };
+
+        AccessControlPolicy {
+            owner: Some(OwnerResult {
+                id: owner_id.to_string(),
+                display_name: display_name(owner_id).unwrap_or_default(),
+            }),
+            grants: acl
+                .grants
+                .iter()
+                .map(|grant| GrantResult {
+                    display_name: match &grant.grantee {
+                        Grantee::User(id) => display_name(id),
+                        _ => None,
+                    },
+                    ..grant.into()
+                })
+                .collect(),
+        }
+    }
+
+    async fn get_bucket_versioning(&self, bucket: &str) -> Result<VersioningConfiguration, Error> {
+        Ok(VersioningConfiguration {
+            versioning: self.find_bucket(bucket).await?.versioning,
+        })
+    }
+
+    async fn put_bucket_versioning(&self, bucket: &str, body: &[u8]) -> Result<(), Error> {
+        let configuration = VersioningConfiguration::from_xml(&String::from_utf8_lossy(body))
+            .ok_or(Error::MalformedXML)?;
+
+        self.storage
+            .set_bucket_versioning(bucket, configuration.versioning)
+            .map(|_| ())
+    }
+
+    async fn get_bucket_lifecycle(&self, bucket: &str) -> Result<LifecycleConfiguration, Error> {
+        let lifecycle = self
+            .find_bucket(bucket)
+            .await?
+            .lifecycle
+            .ok_or(Error::NoSuchLifecycleConfiguration)?;
+
+        Ok((&lifecycle).into())
+    }
+
+    async fn put_bucket_lifecycle(&self, bucket: &str, body: &[u8]) -> Result<(), Error> {
+        let configuration = LifecycleConfiguration::from_xml(&String::from_utf8_lossy(body))
+            .ok_or(Error::MalformedXML)?;
+
+        self.storage
+            .set_bucket_lifecycle(bucket, Some(configuration.lifecycle))
+            .map(|_| ())
+    }
+
+    async fn delete_bucket_lifecycle(&self, bucket: &str) -> Result<(), Error> {
+        self.storage.set_bucket_lifecycle(bucket, None).map(|_| ())
+    }
+
+    async fn get_bucket_policy(&self, bucket: &str) -> Result<PolicyDocument, Error> {
+        let policy = self
+            .find_bucket(bucket)
+            .await?
+            .policy
+            .ok_or(Error::NoSuchBucketPolicy)?;
+
+        Ok((&policy).into())
+    }
+
+    async fn put_bucket_policy(&self, bucket: &str, body: &[u8]) -> Result<(), Error> {
+        let document = PolicyDocument::from_json(&String::from_utf8_lossy(body))?;
+        validate_policy(&document.policy, bucket)?;
+
+        self.storage
+            .set_bucket_policy(bucket, Some(document.policy))
+            .map(|_| ())
+    }
+
+    async fn delete_bucket_policy(&self, bucket: &str) -> Result<(), Error> {
+        self.storage.set_bucket_policy(bucket, None).map(|_| ())
     }
 
-    async fn list_objects(&self, bucket: &str) -> Result<ListBucketResult, String> {
-        let storage = self.storage.lock().await;
+    async fn list_objects(
+        &self,
+        bucket: &str,
+        params: &ListObjectsParams,
+    ) -> Result<ListBucketResult, Error> {
+        let listing = self.storage.list_objects(
+            bucket,
+            &params.prefix,
+            params.delimiter.as_deref(),
+            params.marker.as_deref(),
+            params.max_keys,
+        )?;
 
         Ok(ListBucketResult {
-            is_truncated: false,
-            contents: storage
-                .list_objects(bucket)
+            is_truncated: listing.next_marker.is_some(),
+            contents: listing.objects.iter().map(|o| o.into()).collect(),
+            common_prefixes: listing
+                .common_prefixes
+                .into_iter()
+                .map(|prefix| CommonPrefixResult { prefix })
+                .collect(),
+            name: bucket.to_string(),
+            prefix: params.prefix.to_string(),
+            delimiter: params.delimiter.clone(),
+            max_keys: params.max_keys,
+            marker: params.marker.clone(),
+            next_marker: listing.next_marker,
+        })
+    }
+
+    async fn list_objects_v2(
+        &self,
+        bucket: &str,
+        params: &ListObjectsParams,
+    ) -> Result<ListBucketResultV2, Error> {
+        let token_marker = params
+            .continuation_token
+            .as_ref()
+            .map(|token| String::from_utf8(hex_decode(token)))
+            .transpose()
+            .map_err(|_| {
+                Error::InvalidArgument("The continuation token provided is incorrect".to_string())
+            })?;
+        let marker = match (token_marker, params.marker.as_ref()) {
+            (Some(token), Some(start_after)) => Some(token.max(start_after.to_string())),
+            (token, start_after) => token.or_else(|| start_after.cloned()),
+        };
+
+        let listing = self.storage.list_objects(
+            bucket,
+            &params.prefix,
+            params.delimiter.as_deref(),
+            marker.as_deref(),
+            params.max_keys,
+        )?;
+
+        Ok(ListBucketResultV2 {
+            is_truncated: listing.next_marker.is_some(),
+            key_count: listing.objects.len() + listing.common_prefixes.len(),
+            contents: listing.objects.iter().map(|o| o.into()).collect(),
+            common_prefixes: listing
+                .common_prefixes
+                .into_iter()
+                .map(|prefix| CommonPrefixResult { prefix })
+                .collect(),
+            name: bucket.to_string(),
+            prefix: params.prefix.to_string(),
+            delimiter: params.delimiter.clone(),
+            max_keys: params.max_keys,
+            continuation_token: params.continuation_token.clone(),
+            next_continuation_token: listing.next_marker.map(|m| hex_encode(m.as_bytes())),
+            start_after: params.marker.clone(),
+        })
+    }
+
+    async fn list_object_versions(
+        &self,
+        bucket: &str,
+        params: &ListObjectsParams,
+    ) -> Result<ListVersionsResult, Error> {
+        let listing = self.storage.list_object_versions(
+            bucket,
+            &params.prefix,
+            params.delimiter.as_deref(),
+            params.marker.as_deref(),
+            params.version_id_marker.as_deref(),
+            params.max_keys,
+        )?;
+        let (next_key_marker, next_version_id_marker) = listing.next_marker.unzip();
+
+        Ok(ListVersionsResult {
+            is_truncated: next_key_marker.is_some(),
+            versions: listing
+                .versions
                 .iter()
-                .map(|o| o.into())
+                .map(|v| {
+                    let mut version = VersionResult::from(&v.object);
+                    version.is_latest = v.is_latest;
+                    version
+                })
+                .collect(),
+            common_prefixes: listing
+                .common_prefixes
+                .into_iter()
+                .map(|prefix| CommonPrefixResult { prefix })
                 .collect(),
             name: bucket.to_string(),
+            prefix: params.prefix.to_string(),
+            delimiter: params.delimiter.clone(),
+            max_keys: params.max_keys,
+            key_marker: params.marker.clone(),
+            version_id_marker: params.version_id_marker.clone(),
+            next_key_marker,
+            next_version_id_marker,
         })
     }
 
-    async fn delete_bucket(&self, bucket: &str) -> Result<(), String> {
-        let mut storage = self.storage.lock().await;
-        Ok(storage.delete_bucket(&bucket))
+    async fn delete_bucket(&self, bucket: &str) -> Result<(), Error> {
+        self.storage.delete_bucket(bucket).await
     }
 
     async fn put_object(
@@ -88,117 +743,682 @@ impl App {
         user: &User,
         bucket: &str,
         key: &str,
+        options: ObjectOptions,
+        conditions: &Conditions,
+        body: PayloadStream,
+    ) -> Result<Object, Error> {
+        self.storage
+            .put_object(user, bucket, key, options, conditions, body)
+            .await
+    }
+
+    async fn copy_object(
+        &self,
+        user: &User,
+        source: &CopySource,
+        bucket: &str,
+        key: &str,
+        options: ObjectOptions,
+    ) -> Result<(Object, Object), Error> {
+        self.storage
+            .copy_object(user, source, bucket, key, options)
+            .await
+    }
+
+    async fn get_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: Option<&str>,
+        range: Option<ObjectRange>,
+        conditions: &Conditions,
+    ) -> Result<(Object, Option<(i64, i64)>, ObjectStream), Error> {
+        self.storage
+            .get_object(bucket, key, version_id, range, conditions)
+            .await
+    }
+
+    async fn head_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: Option<&str>,
+    ) -> Result<Object, Error> {
+        self.storage.head_object(bucket, key, version_id)
+    }
+
+    async fn find_bucket(&self, bucket: &str) -> Result<Bucket, Error> {
+        self.storage.find_bucket(bucket)
+    }
+
+    async fn delete_object(
+        &self,
+        user: &User,
+        bucket: &str,
+        key: &str,
+        version_id: Option<&str>,
+    ) -> Result<Option<Object>, Error> {
+        self.storage
+            .delete_object(user, bucket, key, version_id)
+            .await
+    }
+
+    async fn find_upload(&self, bucket: &str, key: &str, upload_id: &str) -> Result<Upload, Error> {
+        match self.storage.find_upload(upload_id)? {
+            upload if upload.bucket == bucket && upload.key == key => Ok(upload),
+            _ => Err(Error::NoSuchUpload),
+        }
+    }
+
+    // keys the user may not delete are reported alongside the others rather
+    // than failing the whole request
+    async fn delete_objects(
+        &self,
+        user: &User,
+        bucket: &str,
+        context: &HashMap<String, String>,
         body: &[u8],
-    ) -> Result<(), String> {
-        let mut storage = self.storage.lock().await;
+    ) -> Result<DeleteResult, Error> {
+        let request =
+            Delete::from_xml(&String::from_utf8_lossy(body)).ok_or(Error::MalformedXML)?;
+        if request.objects.len() > MAX_KEYS {
+            return Err(Error::MalformedXML);
+        }
 
-        storage.put_object(&user, &bucket, &key, body);
+        let mut allowed = Vec::with_capacity(request.objects.len());
+        let mut result = DeleteResult::default();
+        let delete_error =
+            |key: &str, version_id: &Option<String>, error: &Error| DeleteErrorResult {
+                key: key.to_string(),
+                version_id: version_id.clone(),
+                code: error.code().to_string(),
+                message: error.message(),
+            };
+        for (key, version_id) in request.objects {
+            let action = match version_id {
+                Some(_) => Action::DeleteObjectVersion,
+                None => Action::DeleteObject,
+            };
+            match self
+                .authorize_action(
+                    user,
+                    action,
+                    Some(bucket),
+                    Some(&key),
+                    version_id.as_deref(),
+                    context,
+                )
+                .await
+            {
+                Ok(()) => allowed.push((key, version_id)),
+                Err(e) => result.errors.push(delete_error(&key, &version_id, &e)),
+            }
+        }
 
-        Ok(())
+        let outcomes = self.storage.delete_objects(user, bucket, &allowed).await?;
+        for ((key, version_id), outcome) in allowed.iter().zip(outcomes) {
+            match outcome {
+                Ok(_) if request.quiet => {}
+                Ok(object) => result.deleted.push(DeletedResult::new(
+                    key,
+                    version_id.as_deref(),
+                    object.as_ref(),
+                )),
+                Err(e) => result.errors.push(delete_error(key, version_id, &e)),
+            }
+        }
+
+        Ok(result)
     }
 
-    async fn get_object(
+    async fn create_multipart_upload(
         &self,
+        user: &User,
         bucket: &str,
         key: &str,
-        buf: &mut Vec<u8>,
-    ) -> Result<Object, String> {
-        let storage = self.storage.lock().await;
+        options: ObjectOptions,
+    ) -> Result<InitiateMultipartUploadResult, Error> {
+        Ok((&self
+            .storage
+            .create_multipart_upload(user, bucket, key, options)
+            .await?)
+            .into())
+    }
 
-        Ok(storage.get_object(&bucket, &key, buf))
+    async fn upload_part(
+        &self,
+        upload: &Upload,
+        part_number: i64,
+        content_md5: Option<&str>,
+        body: PayloadStream,
+    ) -> Result<Part, Error> {
+        self.storage
+            .upload_part(upload, part_number, content_md5, body)
+            .await
     }
 
-    async fn delete_object(&self, bucket: &str, key: &str) -> Result<(), String> {
-        let mut storage = self.storage.lock().await;
+    async fn upload_part_copy(
+        &self,
+        upload: &Upload,
+        part_number: i64,
+        source: &CopySource,
+        range: Option<ObjectRange>,
+    ) -> Result<(Object, Part), Error> {
+        self.storage
+            .upload_part_copy(upload, part_number, source, range)
+            .await
+    }
 
-        storage.delete_object(&bucket, &key);
-        Ok(())
+    async fn complete_multipart_upload(
+        &self,
+        upload: &Upload,
+        conditions: &Conditions,
+        body: &[u8],
+    ) -> Result<(Object, CompleteMultipartUploadResult), Error> {
+        let request = CompleteMultipartUpload::from_xml(&String::from_utf8_lossy(body))
+            .ok_or(Error::MalformedXML)?;
+
+        let object = self
+            .storage
+            .complete_multipart_upload(upload, &request.parts, conditions)
+            .await?;
+        let etag = object.etag.to_string();
+
+        Ok((
+            object,
+            CompleteMultipartUploadResult {
+                location: format!("/{}/{}", upload.bucket, upload.key),
+                bucket: upload.bucket.to_string(),
+                key: upload.key.to_string(),
+                etag,
+            },
+        ))
     }
 
-    async fn find_user(&self, access_key: &str) -> Result<User, String> {
-        let storage = self.storage.lock().await;
+    async fn abort_multipart_upload(&self, upload: &Upload) -> Result<(), Error> {
+        self.storage.abort_multipart_upload(upload).await
+    }
 
-        Ok(storage.find_user(access_key).unwrap().to_owned())
+    async fn find_user(&self, access_key: &str) -> Result<User, Error> {
+        self.storage.find_user(access_key)
     }
 
-    pub async fn handle(self, req: Request<Body>) -> Result<Response<Body>, Infallible> {
-        let auth_str = self.get_auth_header(&req);
-        let auth = Auth::parse(&auth_str);
+    fn version_headers(
+        &self,
+        mut response: response::Builder,
+        object: &Object,
+    ) -> response::Builder {
+        if let Some(version_id) = &object.version_id {
+            response = response.header("x-amz-version-id", version_id);
+        }
+        if object.delete_marker {
+            response = response.header("x-amz-delete-marker", "true");
+        }
+        response
+    }
+
+    fn copy_source_headers(
+        &self,
+        response: response::Builder,
+        source: &Object,
+    ) -> response::Builder {
+        match &source.version_id {
+            Some(version_id) => response.header("x-amz-copy-source-version-id", version_id),
+            None => response,
+        }
+    }
+
+    fn object_response(&self, object: &Object) -> response::Builder {
+        let mut response = self.version_headers(Response::builder(), object);
+        if !object.tags.is_empty() {
+            response = response.header("x-amz-tagging-count", object.tags.len());
+        }
+
+        if !object.etag.is_empty() {
+            response = response.header("ETag", format!("\"{}\"", object.etag));
+        }
+
+        let metadata = &object.metadata;
+        for (name, value) in [
+            ("Content-Encoding", &metadata.content_encoding),
+            ("Content-Disposition", &metadata.content_disposition),
+            ("Cache-Control", &metadata.cache_control),
+        ] {
+            if let Some(value) = value {
+                response = response.header(name, value);
+            }
+        }
+        for (name, value) in &metadata.user {
+            response = response.header(format!("{}{}", META_HEADER_PREFIX, name), value);
+        }
+
+        response
+            .status(StatusCode::OK)
+            .header("Last-Modified", http_date(object.last_modified))
+            .header("Content-Length", object.size)
+            .header("Accept-Ranges", "bytes")
+            .header(
+                "Content-Type",
+                metadata
+                    .content_type
+                    .as_deref()
+                    .unwrap_or(DEFAULT_CONTENT_TYPE),
+            )
+    }
+
+    // a 304 still identifies the object, so caches can refresh what they hold
+    fn not_modified_response(&self, object: &Object) -> Response<Body> {
+        self.version_headers(Response::builder(), object)
+            .status(StatusCode::NOT_MODIFIED)
+            .header("ETag", format!("\"{}\"", object.etag))
+            .header("Last-Modified", http_date(object.last_modified))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    // GET and HEAD can replace some of the stored headers through the query
+    fn override_headers(
+        &self,
+        mut response: response::Builder,
+        req: &Request<Body>,
+    ) -> Result<response::Builder, Error> {
+        let query = self.get_query_params(req);
+
+        if let Some(headers) = response.headers_mut() {
+            for (param, header) in RESPONSE_OVERRIDES.iter() {
+                if let Some(value) = query.get(*param) {
+                    let value = HeaderValue::from_str(value).map_err(|_| {
+                        Error::InvalidArgument(format!("Invalid value for {}.", param))
+                    })?;
+                    headers.insert(*header, value);
+                }
+            }
+        }
+        Ok(response)
+    }
 
-        let user = self.find_user(&auth.access_key).await.unwrap();
+    fn error_response(&self, error: &Error, resource: &str, request_id: &str) -> Response<Body> {
+        if let Error::InternalError(cause) = error {
+            eprintln!("request {} failed: {}", request_id, cause);
+        }
 
-        if auth_str != "" && !self.check_signature(&user, &auth, &req) {
-            return Ok(Response::builder().status(401).body(Body::empty()).unwrap());
+        let result = ErrorResult::new(error, resource, request_id);
+        if let Error::NotModified = error {
+            return Response::builder()
+                .status(result.status)
+                .header("x-amz-request-id", request_id)
+                .body(Body::empty())
+                .unwrap();
         }
 
-        let result = match self.detect_operation(&req) {
+        Response::builder()
+            .status(result.status)
+            .header("Content-Type", "application/xml")
+            .header("x-amz-request-id", request_id)
+            .body(Body::from(result.to_xml()))
+            .unwrap()
+    }
+
+    pub async fn handle(self, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+        let resource = req.uri().path().to_string();
+        let request_id = format!(
+            "{:X}",
+            Md5::digest(format!("{}/{:?}", resource, Local::now()).as_bytes())
+        )[..16]
+            .to_string();
+
+        match self.process(req).await {
+            Ok(mut response) => {
+                response
+                    .headers_mut()
+                    .insert("x-amz-request-id", request_id.parse().unwrap());
+                Ok(response)
+            }
+            Err(error) => Ok(self.error_response(&error, &resource, &request_id)),
+        }
+    }
+
+    async fn process(&self, req: Request<Body>) -> Result<Response<Body>, Error> {
+        let query = self.get_query_params(&req);
+        let auth = match self.get_auth_header(&req) {
+            header if !header.is_empty() => Some(Auth::parse(&header)?),
+            _ if query.contains_key("X-Amz-Signature") => Some(Auth::parse_query(&query)?),
+            _ => None,
+        };
+
+        let user = match &auth {
+            Some(auth) => {
+                let user = self.find_user(&auth.access_key).await?;
+                self.check_signature(&user, auth, &req)?;
+                user
+            }
+            None => User::anonymous(),
+        };
+
+        let operation = self.detect_operation(&req)?;
+        let context = self.get_policy_context(&req, &operation);
+        self.authorize(&user, &operation, &context).await?;
+
+        let result = match operation {
             Operation::ListBuckets => Response::builder()
                 .status(StatusCode::OK)
-                .body(Body::from(self.list_buckets(&user).await.unwrap().to_xml()))
+                .body(Body::from(self.list_buckets(&user).await?.to_xml()))
                 .unwrap(),
             Operation::CreateBucket(bucket) => {
-                self.create_bucket(&user, &bucket).await.unwrap();
+                let acl = self.get_canned_acl(&req)?;
+                let entire_body = read_body(self.payload(auth.as_ref(), &user, req)?).await?;
+                self.create_bucket(&user, &bucket, acl, &entire_body)
+                    .await?;
+
                 Response::builder()
                     .status(StatusCode::OK)
+                    .header("Location", format!("/{}", bucket))
                     .body(Body::empty())
                     .unwrap()
             }
+            Operation::GetBucketLocation(bucket) => Response::builder()
+                .status(StatusCode::OK)
+                .body(Body::from(
+                    LocationConstraint {
+                        location: self.find_bucket(&bucket).await?.location,
+                    }
+                    .to_xml(),
+                ))
+                .unwrap(),
             Operation::DeleteBucket(bucket) => {
-                self.delete_bucket(&bucket).await.unwrap();
+                self.delete_bucket(&bucket).await?;
 
                 Response::builder()
                     .status(StatusCode::NO_CONTENT)
                     .body(Body::empty())
                     .unwrap()
             }
-            Operation::ListObjects(bucket) => Response::builder()
+            Operation::ListObjects(bucket, params) => Response::builder()
+                .status(StatusCode::OK)
+                .body(Body::from(
+                    self.list_objects(&bucket, &params).await?.to_xml(),
+                ))
+                .unwrap(),
+            Operation::ListObjectsV2(bucket, params) => Response::builder()
                 .status(StatusCode::OK)
                 .body(Body::from(
-                    self.list_objects(&bucket).await.unwrap().to_xml(),
+                    self.list_objects_v2(&bucket, &params).await?.to_xml(),
                 ))
                 .unwrap(),
             Operation::PutObject(bucket, key) => {
-                let entire_body = req
-                    .into_body()
-                    .try_fold(Vec::new(), |mut data, chunk| async move {
-                        data.extend_from_slice(&chunk);
-                        Ok(data)
-                    })
-                    .await
-                    .unwrap();
+                let options = self.object_options(&user, &bucket, &req).await?;
+                let conditions = self.get_conditions(&req);
+                let object = self
+                    .put_object(
+                        &user,
+                        &bucket,
+                        &key,
+                        options,
+                        &conditions,
+                        self.payload(auth.as_ref(), &user, req)?,
+                    )
+                    .await?;
+                self.version_headers(Response::builder(), &object)
+                    .status(StatusCode::OK)
+                    .header("ETag", format!("\"{}\"", object.etag))
+                    .body(Body::empty())
+                    .unwrap()
+            }
+            Operation::CopyObject(bucket, key, params) => {
+                let source = self.get_copy_source(&req, &params)?;
+                let options = self.object_options(&user, &bucket, &req).await?;
+                let (source, object) = self
+                    .copy_object(&user, &source, &bucket, &key, options)
+                    .await?;
 
-                let mut hasher = Md5::new();
-                hasher.update(&entire_body);
-                let content_md5 = format!("{:x}", hasher.finalize());
+                self.copy_source_headers(
+                    self.version_headers(Response::builder(), &object),
+                    &source,
+                )
+                .status(StatusCode::OK)
+                .body(Body::from(CopyObjectResult::from(&object).to_xml()))
+                .unwrap()
+            }
+            Operation::GetObject(bucket, key, version_id) => {
+                let range = self.get_range(&req)?;
+                let conditions = self.get_conditions(&req);
+                let got = self
+                    .get_object(&bucket, &key, version_id.as_deref(), range, &conditions)
+                    .await;
+                let (object, byte_range, stream) = match got {
+                    Err(Error::NotModified) => {
+                        let object = self
+                            .head_object(&bucket, &key, version_id.as_deref())
+                            .await?;
+                        return Ok(self.not_modified_response(&object));
+                    }
+                    got => got?,
+                };
+
+                let mut response = self.object_response(&object);
+                if let Some((start, end)) = byte_range {
+                    let headers = response.headers_mut().unwrap();
+                    headers.insert("Content-Length", HeaderValue::from(end - start + 1));
+                    headers.insert(
+                        "Content-Range",
+                        HeaderValue::from_str(&format!("bytes {}-{}/{}", start, end, object.size))
+                            .unwrap(),
+                    );
+                    if let Some(ObjectRange::Part(_)) = range {
+                        headers.insert(
+                            "x-amz-mp-parts-count",
+                            HeaderValue::from(object.part_sizes.len().max(1)),
+                        );
+                    }
+                    response = response.status(StatusCode::PARTIAL_CONTENT);
+                }
+
+                self.override_headers(response, &req)?
+                    .body(Body::wrap_stream(stream))
+                    .unwrap()
+            }
+            Operation::HeadObject(bucket, key, version_id) => {
+                let object = self
+                    .head_object(&bucket, &key, version_id.as_deref())
+                    .await?;
+                match self.get_conditions(&req).check(Some(&object), true) {
+                    Err(Error::NotModified) => return Ok(self.not_modified_response(&object)),
+                    checked => checked?,
+                }
+
+                self.override_headers(self.object_response(&object), &req)?
+                    .body(Body::empty())
+                    .unwrap()
+            }
+            Operation::HeadBucket(_) => Response::builder()
+                .status(StatusCode::OK)
+                .body(Body::empty())
+                .unwrap(),
+            Operation::GetBucketAcl(bucket) => Response::builder()
+                .status(StatusCode::OK)
+                .body(Body::from(self.get_bucket_acl(&bucket).await?.to_xml()))
+                .unwrap(),
+            Operation::PutBucketAcl(bucket) => {
+                let acl = self.get_canned_acl(&req)?;
+                let entire_body = read_body(self.payload(auth.as_ref(), &user, req)?).await?;
+                self.put_bucket_acl(&bucket, acl, &entire_body).await?;
 
-                self.put_object(&user, &bucket, &key, &entire_body)
-                    .await
-                    .unwrap();
                 Response::builder()
                     .status(StatusCode::OK)
-                    .header("ETag", content_md5)
                     .body(Body::empty())
                     .unwrap()
             }
-            Operation::GetObject(bucket, key) => {
-                let mut buf = vec![];
-                let object = self.get_object(&bucket, &key, &mut buf).await.unwrap();
+            Operation::GetObjectAcl(bucket, key) => Response::builder()
+                .status(StatusCode::OK)
+                .body(Body::from(
+                    self.get_object_acl(&bucket, &key).await?.to_xml(),
+                ))
+                .unwrap(),
+            Operation::PutObjectAcl(bucket, key) => {
+                let acl = self.get_canned_acl(&req)?;
+                let entire_body = read_body(self.payload(auth.as_ref(), &user, req)?).await?;
+                self.put_object_acl(&bucket, &key, acl, &entire_body)
+                    .await?;
 
                 Response::builder()
                     .status(StatusCode::OK)
-                    .header(
-                        "Last-Modified",
-                        object
-                            .last_modified
-                            .with_timezone(&Utc)
-                            .format("%a, %d %b %Y %H:%M:%S GMT")
-                            .to_string(),
+                    .body(Body::empty())
+                    .unwrap()
+            }
+            Operation::ListObjectVersions(bucket, params) => Response::builder()
+                .status(StatusCode::OK)
+                .body(Body::from(
+                    self.list_object_versions(&bucket, &params).await?.to_xml(),
+                ))
+                .unwrap(),
+            Operation::GetBucketVersioning(bucket) => Response::builder()
+                .status(StatusCode::OK)
+                .body(Body::from(
+                    self.get_bucket_versioning(&bucket).await?.to_xml(),
+                ))
+                .unwrap(),
+            Operation::PutBucketVersioning(bucket) => {
+                let entire_body = read_body(self.payload(auth.as_ref(), &user, req)?).await?;
+                self.put_bucket_versioning(&bucket, &entire_body).await?;
+
+                Response::builder()
+                    .status(StatusCode::OK)
+                    .body(Body::empty())
+                    .unwrap()
+            }
+            Operation::GetBucketLifecycle(bucket) => Response::builder()
+                .status(StatusCode::OK)
+                .body(Body::from(
+                    self.get_bucket_lifecycle(&bucket).await?.to_xml(),
+                ))
+                .unwrap(),
+            Operation::PutBucketLifecycle(bucket) => {
+                let entire_body = read_body(self.payload(auth.as_ref(), &user, req)?).await?;
+                self.put_bucket_lifecycle(&bucket, &entire_body).await?;
+
+                Response::builder()
+                    .status(StatusCode::OK)
+                    .body(Body::empty())
+                    .unwrap()
+            }
+            Operation::DeleteBucketLifecycle(bucket) => {
+                self.delete_bucket_lifecycle(&bucket).await?;
+
+                Response::builder()
+                    .status(StatusCode::NO_CONTENT)
+                    .body(Body::empty())
+                    .unwrap()
+            }
+            Operation::GetBucketPolicy(bucket) => Response::builder()
+                .status(StatusCode::OK)
+                .header("Content-Type", "application/json")
+                .body(Body::from(self.get_bucket_policy(&bucket).await?.to_json()))
+                .unwrap(),
+            Operation::PutBucketPolicy(bucket) => {
+                let entire_body = read_body(self.payload(auth.as_ref(), &user, req)?).await?;
+                self.put_bucket_policy(&bucket, &entire_body).await?;
+
+                Response::builder()
+                    .status(StatusCode::NO_CONTENT)
+                    .body(Body::empty())
+                    .unwrap()
+            }
+            Operation::DeleteBucketPolicy(bucket) => {
+                self.delete_bucket_policy(&bucket).await?;
+
+                Response::builder()
+                    .status(StatusCode::NO_CONTENT)
+                    .body(Body::empty())
+                    .unwrap()
+            }
+            Operation::DeleteObject(bucket, key, version_id) => {
+                let response = match self
+                    .delete_object(&user, &bucket, &key, version_id.as_deref())
+                    .await?
+                {
+                    Some(object) => self.version_headers(Response::builder(), &object),
+                    None => Response::builder(),
+                };
+
+                response
+                    .status(StatusCode::NO_CONTENT)
+                    .body(Body::empty())
+                    .unwrap()
+            }
+            Operation::DeleteObjects(bucket) => {
+                let content_md5 = self.get_content_md5(&req);
+                let entire_body = read_body(self.payload(auth.as_ref(), &user, req)?).await?;
+                if content_md5.is_some_and(|md5| md5 != base64_encode(&Md5::digest(&entire_body))) {
+                    return Err(Error::BadDigest);
+                }
+                let result = self
+                    .delete_objects(&user, &bucket, &context, &entire_body)
+                    .await?;
+
+                Response::builder()
+                    .status(StatusCode::OK)
+                    .body(Body::from(result.to_xml()))
+                    .unwrap()
+            }
+            Operation::CreateMultipartUpload(bucket, key) => {
+                let options = self.object_options(&user, &bucket, &req).await?;
+
+                Response::builder()
+                    .status(StatusCode::OK)
+                    .body(Body::from(
+                        self.create_multipart_upload(&user, &bucket, &key, options)
+                            .await?
+                            .to_xml(),
+                    ))
+                    .unwrap()
+            }
+            Operation::UploadPart(bucket, key, upload_id, part_number) => {
+                let upload = self.find_upload(&bucket, &key, &upload_id).await?;
+                let content_md5 = self.get_content_md5(&req);
+                let part = self
+                    .upload_part(
+                        &upload,
+                        part_number,
+                        content_md5.as_deref(),
+                        self.payload(auth.as_ref(), &user, req)?,
                     )
-                    .body(Body::from(buf))
+                    .await?;
+
+                Response::builder()
+                    .status(StatusCode::OK)
+                    .header("ETag", format!("\"{}\"", part.etag))
+                    .body(Body::empty())
                     .unwrap()
             }
-            Operation::DeleteObject(bucket, key) => {
-                self.delete_object(&bucket, &key).await.unwrap();
+            Operation::UploadPartCopy(bucket, key, upload_id, part_number, params) => {
+                let upload = self.find_upload(&bucket, &key, &upload_id).await?;
+                let source = self.get_copy_source(&req, &params)?;
+                let range = self.get_copy_range(&req)?;
+                let (source, part) = self
+                    .upload_part_copy(&upload, part_number, &source, range)
+                    .await?;
+
+                self.copy_source_headers(Response::builder(), &source)
+                    .status(StatusCode::OK)
+                    .body(Body::from(CopyPartResult::from(&part).to_xml()))
+                    .unwrap()
+            }
+            Operation::CompleteMultipartUpload(bucket, key, upload_id) => {
+                let upload = self.find_upload(&bucket, &key, &upload_id).await?;
+                let conditions = self.get_conditions(&req);
+                let entire_body = read_body(self.payload(auth.as_ref(), &user, req)?).await?;
+                let (object, result) = self
+                    .complete_multipart_upload(&upload, &conditions, &entire_body)
+                    .await?;
+
+                self.version_headers(Response::builder(), &object)
+                    .status(StatusCode::OK)
+                    .body(Body::from(result.to_xml()))
+                    .unwrap()
+            }
+            Operation::AbortMultipartUpload(bucket, key, upload_id) => {
+                let upload = self.find_upload(&bucket, &key, &upload_id).await?;
+                self.abort_multipart_upload(&upload).await?;
 
                 Response::builder()
                     .status(StatusCode::NO_CONTENT)
@@ -210,31 +1430,182 @@ impl App {
         Ok(result)
     }
 
-    fn detect_operation(&self, req: &Request<Body>) -> Operation {
-        let mut iter = req
-            .uri()
-            .path()
+    fn detect_operation(&self, req: &Request<Body>) -> Result<Operation, Error> {
+        let path = url_decode(req.uri().path());
+        let mut iter = path
             .strip_prefix('/')
-            .unwrap()
+            .unwrap_or(&path)
             .splitn(2, '/')
-            .filter(|&c| c != "");
+            .filter(|c| !c.is_empty());
         let bucket = iter.next();
         let key = iter.next();
+        let query = self.get_query_params(req);
 
-        match (req.method(), bucket, key) {
-            (&Method::GET, Some(bucket), Some(key)) => {
-                Operation::GetObject(bucket.to_string(), key.to_string())
+        let operation = match (req.method(), bucket, key) {
+            (&Method::POST, Some(bucket), Some(key)) if query.contains_key("uploads") => {
+                Operation::CreateMultipartUpload(bucket.to_string(), key.to_string())
+            }
+            (&Method::POST, Some(bucket), Some(key)) if query.contains_key("uploadId") => {
+                Operation::CompleteMultipartUpload(
+                    bucket.to_string(),
+                    key.to_string(),
+                    query["uploadId"].to_string(),
+                )
+            }
+            (&Method::PUT, Some(bucket), Some(key))
+                if query.contains_key("uploadId")
+                    && query.contains_key("partNumber")
+                    && req.headers().contains_key(COPY_SOURCE_HEADER) =>
+            {
+                Operation::UploadPartCopy(
+                    bucket.to_string(),
+                    key.to_string(),
+                    query["uploadId"].to_string(),
+                    query["partNumber"].parse().unwrap_or(0),
+                    self.get_copy_source_params(req),
+                )
+            }
+            (&Method::PUT, Some(bucket), Some(key))
+                if query.contains_key("uploadId") && query.contains_key("partNumber") =>
+            {
+                Operation::UploadPart(
+                    bucket.to_string(),
+                    key.to_string(),
+                    query["uploadId"].to_string(),
+                    query["partNumber"].parse().unwrap_or(0),
+                )
+            }
+            (&Method::DELETE, Some(bucket), Some(key)) if query.contains_key("uploadId") => {
+                Operation::AbortMultipartUpload(
+                    bucket.to_string(),
+                    key.to_string(),
+                    query["uploadId"].to_string(),
+                )
+            }
+            (&Method::GET, Some(bucket), Some(key)) if query.contains_key("acl") => {
+                Operation::GetObjectAcl(bucket.to_string(), key.to_string())
+            }
+            (&Method::PUT, Some(bucket), Some(key)) if query.contains_key("acl") => {
+                Operation::PutObjectAcl(bucket.to_string(), key.to_string())
+            }
+            (&Method::GET, Some(bucket), Some(key)) => Operation::GetObject(
+                bucket.to_string(),
+                key.to_string(),
+                query.get("versionId").cloned(),
+            ),
+            (&Method::PUT, Some(bucket), Some(key))
+                if req.headers().contains_key(COPY_SOURCE_HEADER) =>
+            {
+                Operation::CopyObject(
+                    bucket.to_string(),
+                    key.to_string(),
+                    self.get_copy_source_params(req),
+                )
             }
             (&Method::PUT, Some(bucket), Some(key)) => {
                 Operation::PutObject(bucket.to_string(), key.to_string())
             }
-            (&Method::DELETE, Some(bucket), Some(key)) => {
-                Operation::DeleteObject(bucket.to_string(), key.to_string())
+            (&Method::HEAD, Some(bucket), Some(key)) => Operation::HeadObject(
+                bucket.to_string(),
+                key.to_string(),
+                query.get("versionId").cloned(),
+            ),
+            (&Method::DELETE, Some(bucket), Some(key)) => Operation::DeleteObject(
+                bucket.to_string(),
+                key.to_string(),
+                query.get("versionId").cloned(),
+            ),
+            (&Method::POST, Some(bucket), None) if query.contains_key("delete") => {
+                Operation::DeleteObjects(bucket.to_string())
+            }
+            (&Method::GET, Some(bucket), None) if query.contains_key("policy") => {
+                Operation::GetBucketPolicy(bucket.to_string())
+            }
+            (&Method::PUT, Some(bucket), None) if query.contains_key("policy") => {
+                Operation::PutBucketPolicy(bucket.to_string())
+            }
+            (&Method::DELETE, Some(bucket), None) if query.contains_key("policy") => {
+                Operation::DeleteBucketPolicy(bucket.to_string())
+            }
+            (&Method::GET, Some(bucket), None) if query.contains_key("location") => {
+                Operation::GetBucketLocation(bucket.to_string())
+            }
+            (&Method::GET, Some(bucket), None) if query.contains_key("versioning") => {
+                Operation::GetBucketVersioning(bucket.to_string())
+            }
+            (&Method::PUT, Some(bucket), None) if query.contains_key("versioning") => {
+                Operation::PutBucketVersioning(bucket.to_string())
+            }
+            (&Method::GET, Some(bucket), None) if query.contains_key("lifecycle") => {
+                Operation::GetBucketLifecycle(bucket.to_string())
+            }
+            (&Method::PUT, Some(bucket), None) if query.contains_key("lifecycle") => {
+                Operation::PutBucketLifecycle(bucket.to_string())
+            }
+            (&Method::DELETE, Some(bucket), None) if query.contains_key("lifecycle") => {
+                Operation::DeleteBucketLifecycle(bucket.to_string())
+            }
+            (&Method::GET, Some(bucket), None) if query.contains_key("versions") => {
+                Operation::ListObjectVersions(
+                    bucket.to_string(),
+                    ListObjectsParams {
+                        marker: query.get("key-marker").cloned(),
+                        version_id_marker: query.get("version-id-marker").cloned(),
+                        ..self.get_list_objects_params(&query)
+                    },
+                )
+            }
+            (&Method::GET, Some(bucket), None) if query.contains_key("acl") => {
+                Operation::GetBucketAcl(bucket.to_string())
+            }
+            (&Method::PUT, Some(bucket), None) if query.contains_key("acl") => {
+                Operation::PutBucketAcl(bucket.to_string())
             }
             (&Method::PUT, Some(bucket), None) => Operation::CreateBucket(bucket.to_string()),
-            (&Method::GET, Some(bucket), None) => Operation::ListObjects(bucket.to_string()),
+            (&Method::GET, Some(bucket), None)
+                if query.get("list-type").map(|t| t.as_str()) == Some("2") =>
+            {
+                Operation::ListObjectsV2(
+                    bucket.to_string(),
+                    ListObjectsParams {
+                        marker: query.get("start-after").cloned(),
+                        continuation_token: query.get("continuation-token").cloned(),
+                        ..self.get_list_objects_params(&query)
+                    },
+                )
+            }
+            (&Method::GET, Some(bucket), None) => Operation::ListObjects(
+                bucket.to_string(),
+                ListObjectsParams {
+                    marker: query.get("marker").cloned(),
+                    ..self.get_list_objects_params(&query)
+                },
+            ),
+            (&Method::HEAD, Some(bucket), None) => Operation::HeadBucket(bucket.to_string()),
             (&Method::DELETE, Some(bucket), None) => Operation::DeleteBucket(bucket.to_string()),
-            (_, _, _) => Operation::ListBuckets,
-        }
+            (&Method::GET, None, None) => Operation::ListBuckets,
+            _ => return Err(Error::MethodNotAllowed),
+        };
+        Ok(operation)
+    }
+}
+
+impl From<hyper::Error> for Error {
+    fn from(e: hyper::Error) -> Self {
+        Error::InternalError(e.to_string())
     }
 }
+
+fn http_date(date: DateTime<Local>) -> String {
+    date.with_timezone(&Utc)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+async fn read_body(body: PayloadStream) -> Result<Vec<u8>, Error> {
+    body.try_fold(Vec::new(), |mut data, chunk| async move {
+        data.extend_from_slice(&chunk);
+        Ok(data)
+    })
+    .await
+}
@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+
+use crate::entities::error::Error;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Permission {
+    FullControl,
+    Read,
+    Write,
+    ReadAcp,
+    WriteAcp,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Grantee {
+    User(String),
+    AllUsers,
+    AuthenticatedUsers,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Grant {
+    pub grantee: Grantee,
+    pub permission: Permission,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Acl {
+    pub grants: Vec<Grant>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CannedAcl {
+    Private,
+    PublicRead,
+    PublicReadWrite,
+    AuthenticatedRead,
+    BucketOwnerRead,
+    BucketOwnerFullControl,
+}
+
+impl CannedAcl {
+    pub fn parse(acl: &str) -> Result<Self, Error> {
+        match acl {
+            "private" => Ok(CannedAcl::Private),
+            "public-read" => Ok(CannedAcl::PublicRead),
+            "public-read-write" => Ok(CannedAcl::PublicReadWrite),
+            "authenticated-read" => Ok(CannedAcl::AuthenticatedRead),
+            "bucket-owner-read" => Ok(CannedAcl::BucketOwnerRead),
+            "bucket-owner-full-control" => Ok(CannedAcl::BucketOwnerFullControl),
+            _ => Err(Error::InvalidArgument(format!(
+                "The canned ACL {} is not supported.",
+                acl
+            ))),
+        }
+    }
+}
+
+impl Acl {
+    // `bucket_owner_id` only matters for the bucket-owner-* ACLs, which have
+    // no effect on buckets themselves or on objects the bucket owner uploads
+    pub fn canned(acl: CannedAcl, owner_id: &str, bucket_owner_id: &str) -> Self {
+        let grant = |grantee, permission| Grant {
+            grantee,
+            permission,
+        };
+
+        let mut grants = vec![grant(
+            Grantee::User(owner_id.to_string()),
+            Permission::FullControl,
+        )];
+        match acl {
+            CannedAcl::Private => {}
+            CannedAcl::PublicRead => grants.push(grant(Grantee::AllUsers, Permission::Read)),
+            CannedAcl::PublicReadWrite => {
+                grants.push(grant(Grantee::AllUsers, Permission::Read));
+                grants.push(grant(Grantee::AllUsers, Permission::Write));
+            }
+            CannedAcl::AuthenticatedRead => {
+                grants.push(grant(Grantee::AuthenticatedUsers, Permission::Read))
+            }
+            CannedAcl::BucketOwnerRead | CannedAcl::BucketOwnerFullControl
+                if bucket_owner_id == owner_id => {}
+            CannedAcl::BucketOwnerRead => grants.push(grant(
+                Grantee::User(bucket_owner_id.to_string()),
+                Permission::Read,
+            )),
+            CannedAcl::BucketOwnerFullControl => grants.push(grant(
+                Grantee::User(bucket_owner_id.to_string()),
+                Permission::FullControl,
+            )),
+        }
+
+        Self { grants }
+    }
+}
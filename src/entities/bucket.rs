@@ -1,8 +1,21 @@
 use std::hash::{Hash, Hasher};
+use std::net::Ipv4Addr;
 
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 
+use crate::entities::acl::Acl;
+use crate::entities::lifecycle::Lifecycle;
+use crate::entities::policy::Policy;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Versioning {
+    #[default]
+    Unversioned,
+    Enabled,
+    Suspended,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Bucket {
     pub name: String,
@@ -10,6 +23,37 @@ pub struct Bucket {
     pub object_count: i64,
     pub size: i64,
     pub creation_date: DateTime<Local>,
+    #[serde(default)]
+    pub acl: Acl,
+    #[serde(default)]
+    pub policy: Option<Policy>,
+    #[serde(default)]
+    pub versioning: Versioning,
+    #[serde(default)]
+    pub lifecycle: Option<Lifecycle>,
+    #[serde(default)]
+    pub location: Option<String>,
+}
+
+impl Bucket {
+    // the S3 naming rules; they also keep buckets clear of the files stored
+    // next to them, like the database
+    pub fn is_valid_name(name: &str) -> bool {
+        let alphanumeric = |c: u8| c.is_ascii_lowercase() || c.is_ascii_digit();
+
+        (3..=63).contains(&name.len())
+            && name
+                .bytes()
+                .all(|c| alphanumeric(c) || c == b'.' || c == b'-')
+            && name.bytes().next().is_some_and(alphanumeric)
+            && name.bytes().last().is_some_and(alphanumeric)
+            && !name.contains("..")
+            && name.parse::<Ipv4Addr>().is_err()
+            && !name.starts_with("xn--")
+            && !name.starts_with("sthree-")
+            && !name.ends_with("-s3alias")
+            && !name.ends_with("--ol-s3")
+    }
 }
 
 impl PartialEq for Bucket {
@@ -0,0 +1,197 @@
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum Error {
+    AccessDenied,
+    AuthorizationHeaderMalformed,
+    AuthorizationQueryParametersError(String),
+    BadDigest,
+    BucketAlreadyExists,
+    BucketAlreadyOwnedByYou,
+    BucketNotEmpty,
+    IncompleteBody,
+    InternalError(String),
+    InvalidAccessKeyId,
+    InvalidArgument(String),
+    InvalidBucketName,
+    InvalidLocationConstraint,
+    InvalidPart,
+    InvalidPartNumber,
+    InvalidPartOrder,
+    InvalidRange,
+    InvalidRequest(String),
+    KeyTooLongError,
+    MalformedACLError,
+    MalformedPolicy(String),
+    MalformedXML,
+    MetadataTooLarge,
+    MethodNotAllowed,
+    NoSuchBucket,
+    NoSuchBucketPolicy,
+    NoSuchKey,
+    NoSuchLifecycleConfiguration,
+    NoSuchUpload,
+    NoSuchVersion,
+    NotModified,
+    PreconditionFailed,
+    RequestNotYetValid,
+    RequestTimeTooSkewed,
+    SignatureDoesNotMatch,
+    XAmzContentSHA256Mismatch,
+}
+
+impl Error {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::AccessDenied => "AccessDenied",
+            Error::AuthorizationHeaderMalformed => "AuthorizationHeaderMalformed",
+            Error::AuthorizationQueryParametersError(_) => "AuthorizationQueryParametersError",
+            Error::BadDigest => "BadDigest",
+            Error::BucketAlreadyExists => "BucketAlreadyExists",
+            Error::BucketAlreadyOwnedByYou => "BucketAlreadyOwnedByYou",
+            Error::BucketNotEmpty => "BucketNotEmpty",
+            Error::IncompleteBody => "IncompleteBody",
+            Error::InternalError(_) => "InternalError",
+            Error::InvalidAccessKeyId => "InvalidAccessKeyId",
+            Error::InvalidArgument(_) => "InvalidArgument",
+            Error::InvalidBucketName => "InvalidBucketName",
+            Error::InvalidLocationConstraint => "InvalidLocationConstraint",
+            Error::InvalidPart => "InvalidPart",
+            Error::InvalidPartNumber => "InvalidPartNumber",
+            Error::InvalidPartOrder => "InvalidPartOrder",
+            Error::InvalidRange => "InvalidRange",
+            Error::InvalidRequest(_) => "InvalidRequest",
+            Error::KeyTooLongError => "KeyTooLongError",
+            Error::MalformedACLError => "MalformedACLError",
+            Error::MalformedPolicy(_) => "MalformedPolicy",
+            Error::MalformedXML => "MalformedXML",
+            Error::MetadataTooLarge => "MetadataTooLarge",
+            Error::MethodNotAllowed => "MethodNotAllowed",
+            Error::NoSuchBucket => "NoSuchBucket",
+            Error::NoSuchBucketPolicy => "NoSuchBucketPolicy",
+            Error::NoSuchKey => "NoSuchKey",
+            Error::NoSuchLifecycleConfiguration => "NoSuchLifecycleConfiguration",
+            Error::NoSuchUpload => "NoSuchUpload",
+            Error::NoSuchVersion => "NoSuchVersion",
+            Error::NotModified => "NotModified",
+            Error::PreconditionFailed => "PreconditionFailed",
+            Error::RequestNotYetValid => "AccessDenied",
+            Error::RequestTimeTooSkewed => "RequestTimeTooSkewed",
+            Error::SignatureDoesNotMatch => "SignatureDoesNotMatch",
+            Error::XAmzContentSHA256Mismatch => "XAmzContentSHA256Mismatch",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Error::AccessDenied => "Access Denied".to_string(),
+            Error::AuthorizationHeaderMalformed => {
+                "The authorization header is malformed.".to_string()
+            }
+            Error::AuthorizationQueryParametersError(message) => message.to_string(),
+            Error::BadDigest => {
+                "The checksum you specified did not match what we received.".to_string()
+            }
+            Error::BucketAlreadyExists => "The requested bucket name is not available.".to_string(),
+            Error::BucketAlreadyOwnedByYou => {
+                "The bucket you tried to create already exists, and you own it.".to_string()
+            }
+            Error::BucketNotEmpty => "The bucket you tried to delete is not empty.".to_string(),
+            Error::IncompleteBody => {
+                "You did not provide the number of bytes specified by the Content-Length HTTP header."
+                    .to_string()
+            }
+            Error::InternalError(_) => {
+                "We encountered an internal error. Please try again.".to_string()
+            }
+            Error::InvalidAccessKeyId => {
+                "The AWS Access Key Id you provided does not exist in our records.".to_string()
+            }
+            Error::InvalidArgument(message) => message.to_string(),
+            Error::InvalidBucketName => "The specified bucket is not valid.".to_string(),
+            Error::InvalidLocationConstraint => {
+                "The specified location constraint is not valid.".to_string()
+            }
+            Error::InvalidPart => {
+                "One or more of the specified parts could not be found.".to_string()
+            }
+            Error::InvalidPartNumber => "The requested partnumber is not satisfiable.".to_string(),
+            Error::InvalidPartOrder => {
+                "The list of parts was not in ascending order.".to_string()
+            }
+            Error::InvalidRange => "The requested range is not satisfiable.".to_string(),
+            Error::InvalidRequest(message) => message.to_string(),
+            Error::KeyTooLongError => "Your key is too long.".to_string(),
+            Error::MalformedACLError => {
+                "The XML you provided was not well-formed or did not validate against our published schema.".to_string()
+            }
+            Error::MalformedPolicy(message) => message.to_string(),
+            Error::MalformedXML => {
+                "The XML you provided was not well-formed or did not validate against our published schema.".to_string()
+            }
+            Error::MetadataTooLarge => {
+                "Your metadata headers exceed the maximum allowed metadata size.".to_string()
+            }
+            Error::MethodNotAllowed => {
+                "The specified method is not allowed against this resource.".to_string()
+            }
+            Error::NoSuchBucket => "The specified bucket does not exist.".to_string(),
+            Error::NoSuchBucketPolicy => "The bucket policy does not exist.".to_string(),
+            Error::NoSuchKey => "The specified key does not exist.".to_string(),
+            Error::NoSuchLifecycleConfiguration => {
+                "The lifecycle configuration does not exist.".to_string()
+            }
+            Error::NoSuchUpload => "The specified multipart upload does not exist.".to_string(),
+            Error::NoSuchVersion => {
+                "The specified version does not exist.".to_string()
+            }
+            Error::NotModified => "Not Modified".to_string(),
+            Error::PreconditionFailed => {
+                "At least one of the pre-conditions you specified did not hold".to_string()
+            }
+            Error::RequestNotYetValid => "Request is not valid yet".to_string(),
+            Error::RequestTimeTooSkewed => {
+                "The difference between the request time and the server's time is too large."
+                    .to_string()
+            }
+            Error::SignatureDoesNotMatch => {
+                "The request signature we calculated does not match the signature you provided."
+                    .to_string()
+            }
+            Error::XAmzContentSHA256Mismatch => {
+                "The provided 'x-amz-content-sha256' header does not match what was computed."
+                    .to_string()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InternalError(cause) => write!(f, "{}: {}", self.code(), cause),
+            _ => write!(f, "{}: {}", self.code(), self.message()),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::InternalError(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::InternalError(e.to_string())
+    }
+}
+
+impl From<sled::Error> for Error {
+    fn from(e: sled::Error) -> Self {
+        Error::InternalError(e.to_string())
+    }
+}
@@ -0,0 +1,27 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Rule {
+    pub id: Option<String>,
+    pub enabled: bool,
+    pub prefix: String,
+    pub tags: BTreeMap<String, String>,
+    pub expiration_days: Option<i64>,
+    pub noncurrent_expiration_days: Option<i64>,
+    pub abort_incomplete_upload_days: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Lifecycle {
+    pub rules: Vec<Rule>,
+}
+
+impl Rule {
+    pub fn matches(&self, key: &str, tags: &BTreeMap<String, String>) -> bool {
+        self.enabled
+            && key.starts_with(&self.prefix)
+            && self.tags.iter().all(|(k, v)| tags.get(k) == Some(v))
+    }
+}
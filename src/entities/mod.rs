@@ -1,3 +1,8 @@
+pub mod acl;
 pub mod bucket;
+pub mod error;
+pub mod lifecycle;
 pub mod object;
+pub mod policy;
+pub mod upload;
 pub mod user;
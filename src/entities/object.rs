@@ -1,8 +1,22 @@
+use std::collections::BTreeMap;
 use std::hash::{Hash, Hasher};
 
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 
+use crate::entities::acl::Acl;
+
+pub const NULL_VERSION: &str = "null";
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Metadata {
+    pub content_type: Option<String>,
+    pub content_encoding: Option<String>,
+    pub content_disposition: Option<String>,
+    pub cache_control: Option<String>,
+    pub user: BTreeMap<String, String>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Object {
     pub key: String,
@@ -10,6 +24,26 @@ pub struct Object {
     pub owner_id: String,
     pub size: i64,
     pub last_modified: DateTime<Local>,
+    #[serde(default)]
+    pub etag: String,
+    #[serde(default)]
+    pub part_sizes: Vec<i64>,
+    #[serde(default)]
+    pub acl: Acl,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    #[serde(default)]
+    pub metadata: Metadata,
+    #[serde(default)]
+    pub version_id: Option<String>,
+    #[serde(default)]
+    pub delete_marker: bool,
+}
+
+impl Object {
+    pub fn version(&self) -> &str {
+        self.version_id.as_deref().unwrap_or(NULL_VERSION)
+    }
 }
 
 impl PartialEq for Object {
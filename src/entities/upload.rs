@@ -0,0 +1,45 @@
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::entities::acl::Acl;
+use crate::entities::object::Metadata;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Upload {
+    pub id: String,
+    pub bucket: String,
+    pub key: String,
+    pub owner_id: String,
+    pub initiated: DateTime<Local>,
+    #[serde(default)]
+    pub acl: Acl,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    #[serde(default)]
+    pub metadata: Metadata,
+}
+
+impl PartialEq for Upload {
+    fn eq(&self, upload: &Upload) -> bool {
+        self.id == upload.id
+    }
+}
+
+impl Eq for Upload {}
+
+impl Hash for Upload {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Part {
+    pub number: i64,
+    pub etag: String,
+    pub size: i64,
+    pub last_modified: DateTime<Local>,
+}
@@ -7,3 +7,20 @@ pub struct User {
     pub access_key: String,
     pub secret_access_key: String,
 }
+
+const ANONYMOUS_ID: &str = "anonymous";
+
+impl User {
+    pub fn anonymous() -> Self {
+        Self {
+            id: ANONYMOUS_ID.to_string(),
+            display_name: ANONYMOUS_ID.to_string(),
+            access_key: String::new(),
+            secret_access_key: String::new(),
+        }
+    }
+
+    pub fn is_anonymous(&self) -> bool {
+        self.id == ANONYMOUS_ID
+    }
+}
@@ -0,0 +1,435 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use crate::entities::acl::{Acl, Grantee, Permission};
+use crate::entities::bucket::Bucket;
+use crate::entities::error::Error;
+use crate::entities::object::Object;
+use crate::entities::policy::{Condition, Effect, Policy, Statement};
+use crate::entities::user::User;
+
+const CONDITION_OPERATORS: [&str; 6] = [
+    "StringEquals",
+    "StringNotEquals",
+    "StringLike",
+    "StringNotLike",
+    "IpAddress",
+    "NotIpAddress",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    ListAllMyBuckets,
+    CreateBucket,
+    DeleteBucket,
+    ListBucket,
+    ListBucketVersions,
+    GetBucketLocation,
+    GetBucketAcl,
+    PutBucketAcl,
+    GetBucketPolicy,
+    PutBucketPolicy,
+    DeleteBucketPolicy,
+    GetBucketVersioning,
+    PutBucketVersioning,
+    GetLifecycleConfiguration,
+    PutLifecycleConfiguration,
+    GetObject,
+    GetObjectVersion,
+    GetObjectAcl,
+    PutObject,
+    PutObjectAcl,
+    DeleteObject,
+    DeleteObjectVersion,
+    AbortMultipartUpload,
+}
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::ListAllMyBuckets => "s3:ListAllMyBuckets",
+            Action::CreateBucket => "s3:CreateBucket",
+            Action::DeleteBucket => "s3:DeleteBucket",
+            Action::ListBucket => "s3:ListBucket",
+            Action::ListBucketVersions => "s3:ListBucketVersions",
+            Action::GetBucketLocation => "s3:GetBucketLocation",
+            Action::GetBucketAcl => "s3:GetBucketAcl",
+            Action::PutBucketAcl => "s3:PutBucketAcl",
+            Action::GetBucketPolicy => "s3:GetBucketPolicy",
+            Action::PutBucketPolicy => "s3:PutBucketPolicy",
+            Action::DeleteBucketPolicy => "s3:DeleteBucketPolicy",
+            Action::GetBucketVersioning => "s3:GetBucketVersioning",
+            Action::PutBucketVersioning => "s3:PutBucketVersioning",
+            Action::GetLifecycleConfiguration => "s3:GetLifecycleConfiguration",
+            Action::PutLifecycleConfiguration => "s3:PutLifecycleConfiguration",
+            Action::GetObject => "s3:GetObject",
+            Action::GetObjectVersion => "s3:GetObjectVersion",
+            Action::GetObjectAcl => "s3:GetObjectAcl",
+            Action::PutObject => "s3:PutObject",
+            Action::PutObjectAcl => "s3:PutObjectAcl",
+            Action::DeleteObject => "s3:DeleteObject",
+            Action::DeleteObjectVersion => "s3:DeleteObjectVersion",
+            Action::AbortMultipartUpload => "s3:AbortMultipartUpload",
+        }
+    }
+
+    fn manages_policy(&self) -> bool {
+        matches!(
+            self,
+            Action::GetBucketPolicy | Action::PutBucketPolicy | Action::DeleteBucketPolicy
+        )
+    }
+}
+
+// `bucket` is the bucket the action targets, or None for account level
+// actions like listing or creating buckets. `object` is the stored object
+// under `key`, if there is one. `context` holds the condition keys known for
+// this request, lowercased, e.g. "aws:sourceip"
+pub fn authorize(
+    user: &User,
+    action: Action,
+    bucket: Option<&Bucket>,
+    object: Option<&Object>,
+    key: Option<&str>,
+    context: &HashMap<String, String>,
+) -> Result<(), Error> {
+    let bucket = match bucket {
+        Some(bucket) => bucket,
+        None if user.is_anonymous() => return Err(Error::AccessDenied),
+        None => return Ok(()),
+    };
+
+    let resource = match key {
+        Some(key) => format!("arn:aws:s3:::{}/{}", bucket.name, key),
+        None => format!("arn:aws:s3:::{}", bucket.name),
+    };
+    let effect = bucket
+        .policy
+        .as_ref()
+        .and_then(|policy| evaluate(policy, user, action, &resource, context));
+    let owner = bucket.owner_id == user.id;
+
+    // owners can't lock themselves out of fixing a policy that denies them
+    let allowed = match effect {
+        Some(Effect::Deny) => owner && action.manages_policy(),
+        _ if owner => true,
+        Some(Effect::Allow) => true,
+        None => acl_allows(user, action, bucket, object),
+    };
+
+    if !allowed {
+        return Err(Error::AccessDenied);
+    }
+    Ok(())
+}
+
+fn acl_allows(user: &User, action: Action, bucket: &Bucket, object: Option<&Object>) -> bool {
+    let object_allows = |permission| {
+        object.is_some_and(|o| o.owner_id == user.id || granted(&o.acl, user, permission))
+    };
+
+    match action {
+        Action::ListBucket | Action::ListBucketVersions => {
+            granted(&bucket.acl, user, Permission::Read)
+        }
+        Action::GetBucketAcl => granted(&bucket.acl, user, Permission::ReadAcp),
+        Action::PutBucketAcl => granted(&bucket.acl, user, Permission::WriteAcp),
+        Action::PutObject
+        | Action::DeleteObject
+        | Action::DeleteObjectVersion
+        | Action::AbortMultipartUpload => granted(&bucket.acl, user, Permission::Write),
+        // a bucket's READ grant only lists it; a bucket policy allowing
+        // s3:GetObject on `bucket/*` is what makes all of its objects public
+        Action::GetObject | Action::GetObjectVersion => object_allows(Permission::Read),
+        Action::GetObjectAcl => object_allows(Permission::ReadAcp),
+        Action::PutObjectAcl => object_allows(Permission::WriteAcp),
+        _ => false,
+    }
+}
+
+fn granted(acl: &Acl, user: &User, permission: Permission) -> bool {
+    acl.grants.iter().any(|grant| {
+        let grantee = match &grant.grantee {
+            Grantee::User(id) => !user.is_anonymous() && id == &user.id,
+            Grantee::AllUsers => true,
+            Grantee::AuthenticatedUsers => !user.is_anonymous(),
+        };
+        grantee && (grant.permission == permission || grant.permission == Permission::FullControl)
+    })
+}
+
+pub fn validate_policy(policy: &Policy, bucket: &str) -> Result<(), Error> {
+    let bucket_arn = format!("arn:aws:s3:::{}", bucket);
+
+    if policy.statements.is_empty() {
+        return Err(Error::MalformedPolicy(
+            "Could not parse the policy: Statement is empty!".to_string(),
+        ));
+    }
+
+    for statement in &policy.statements {
+        if statement.principals.is_empty() {
+            return Err(Error::MalformedPolicy(
+                "Invalid principal in policy".to_string(),
+            ));
+        }
+        if statement.actions.is_empty()
+            || statement
+                .actions
+                .iter()
+                .any(|a| a != "*" && !a.to_lowercase().starts_with("s3:"))
+        {
+            return Err(Error::MalformedPolicy(
+                "Policy has invalid action".to_string(),
+            ));
+        }
+        if statement.resources.is_empty()
+            || statement
+                .resources
+                .iter()
+                .any(|r| r != &bucket_arn && !r.starts_with(&format!("{}/", bucket_arn)))
+        {
+            return Err(Error::MalformedPolicy(
+                "Policy has invalid resource".to_string(),
+            ));
+        }
+
+        for condition in &statement.conditions {
+            if !CONDITION_OPERATORS.contains(&condition.operator.as_str()) {
+                return Err(Error::MalformedPolicy(format!(
+                    "Policy has an invalid condition operator: {}",
+                    condition.operator
+                )));
+            }
+            if condition.operator.ends_with("IpAddress")
+                && condition.values.iter().any(|v| parse_cidr(v).is_none())
+            {
+                return Err(Error::MalformedPolicy(
+                    "Invalid IP address in condition".to_string(),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// an explicit deny in any matching statement wins over every allow
+fn evaluate(
+    policy: &Policy,
+    user: &User,
+    action: Action,
+    resource: &str,
+    context: &HashMap<String, String>,
+) -> Option<Effect> {
+    policy
+        .statements
+        .iter()
+        .filter(|s| statement_matches(s, user, action, resource, context))
+        .map(|s| s.effect)
+        .max_by_key(|effect| *effect == Effect::Deny)
+}
+
+fn statement_matches(
+    statement: &Statement,
+    user: &User,
+    action: Action,
+    resource: &str,
+    context: &HashMap<String, String>,
+) -> bool {
+    let action = action.name().to_lowercase();
+
+    statement
+        .principals
+        .iter()
+        .any(|p| p == "*" || (!user.is_anonymous() && wildcard_match(p, &user.id)))
+        && statement
+            .actions
+            .iter()
+            .any(|a| wildcard_match(&a.to_lowercase(), &action))
+        && statement
+            .resources
+            .iter()
+            .any(|r| wildcard_match(r, resource))
+        && statement
+            .conditions
+            .iter()
+            .all(|c| condition_matches(c, context))
+}
+
+// a missing key fails positive operators and satisfies negated ones
+fn condition_matches(condition: &Condition, context: &HashMap<String, String>) -> bool {
+    let value = match context.get(&condition.key.to_lowercase()) {
+        Some(value) => value,
+        None => return condition.operator.contains("Not"),
+    };
+
+    let any = |f: &dyn Fn(&str) -> bool| condition.values.iter().any(|v| f(v));
+    match condition.operator.as_str() {
+        "StringEquals" => any(&|v| v == value),
+        "StringNotEquals" => !any(&|v| v == value),
+        "StringLike" => any(&|v| wildcard_match(v, value)),
+        "StringNotLike" => !any(&|v| wildcard_match(v, value)),
+        "IpAddress" | "NotIpAddress" => {
+            let ip = match value.parse::<IpAddr>() {
+                Ok(ip) => ip,
+                Err(_) => return false,
+            };
+            let matched = any(&|v| parse_cidr(v).is_some_and(|cidr| cidr_contains(cidr, ip)));
+            matched == (condition.operator == "IpAddress")
+        }
+        _ => false,
+    }
+}
+
+fn parse_cidr(cidr: &str) -> Option<(IpAddr, u32)> {
+    let (ip, prefix) = match cidr.split_once('/') {
+        Some((ip, prefix)) => (ip.parse::<IpAddr>().ok()?, Some(prefix.parse().ok()?)),
+        None => (cidr.parse::<IpAddr>().ok()?, None),
+    };
+    let bits = if ip.is_ipv4() { 32 } else { 128 };
+
+    match prefix.unwrap_or(bits) {
+        prefix if prefix <= bits => Some((ip, prefix)),
+        _ => None,
+    }
+}
+
+fn cidr_contains((network, prefix): (IpAddr, u32), ip: IpAddr) -> bool {
+    let (network, ip, bits) = match (network, ip) {
+        (IpAddr::V4(n), IpAddr::V4(i)) => (u32::from(n) as u128, u32::from(i) as u128, 32),
+        (IpAddr::V6(n), IpAddr::V6(i)) => (u128::from(n), u128::from(i), 128),
+        _ => return false,
+    };
+    let shift = bits - prefix;
+
+    shift == 128 || network >> shift == ip >> shift
+}
+
+// `*` matches any run of characters and `?` exactly one
+fn wildcard_match(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+    let (mut p, mut v) = (0, 0);
+    let mut backtrack = None;
+
+    while v < value.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, v));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == value[v] => {
+                p += 1;
+                v += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    backtrack = Some((star, matched + 1));
+                    p = star + 1;
+                    v = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::acl::CannedAcl;
+    use chrono::Local;
+
+    fn bucket(acl: CannedAcl) -> Bucket {
+        Bucket {
+            name: "bucket".to_string(),
+            owner_id: "alice".to_string(),
+            object_count: 0,
+            size: 0,
+            creation_date: Local::now(),
+            acl: Acl::canned(acl, "alice", "alice"),
+            policy: None,
+            versioning: Default::default(),
+            lifecycle: None,
+            location: None,
+        }
+    }
+
+    fn allowed(user: &User, action: Action, bucket: Option<&Bucket>) -> bool {
+        authorize(user, action, bucket, None, None, &HashMap::new()).is_ok()
+    }
+
+    #[test]
+    fn anonymous_requests_only_reach_public_buckets() {
+        let anonymous = User::anonymous();
+        let private = bucket(CannedAcl::Private);
+        let public = bucket(CannedAcl::PublicRead);
+
+        assert!(!allowed(&anonymous, Action::ListAllMyBuckets, None));
+        assert!(!allowed(&anonymous, Action::CreateBucket, None));
+        assert!(!allowed(&anonymous, Action::ListBucket, Some(&private)));
+        assert!(allowed(&anonymous, Action::ListBucket, Some(&public)));
+        for action in [
+            Action::PutObject,
+            Action::DeleteObject,
+            Action::DeleteBucket,
+            Action::PutBucketAcl,
+            Action::PutBucketPolicy,
+        ] {
+            assert!(!allowed(&anonymous, action, Some(&public)), "{:?}", action);
+        }
+    }
+
+    #[test]
+    fn anonymous_is_not_an_authenticated_user() {
+        let anonymous = User::anonymous();
+        let bucket = bucket(CannedAcl::AuthenticatedRead);
+        let bob = User {
+            id: "bob".to_string(),
+            display_name: "Bob".to_string(),
+            access_key: "BOBKEY".to_string(),
+            secret_access_key: "secret".to_string(),
+        };
+
+        assert!(!allowed(&anonymous, Action::ListBucket, Some(&bucket)));
+        assert!(allowed(&bob, Action::ListBucket, Some(&bucket)));
+        assert!(!allowed(&bob, Action::PutObject, Some(&bucket)));
+    }
+
+    #[test]
+    fn object_reads_follow_the_object_acl() {
+        let anonymous = User::anonymous();
+        let bucket = bucket(CannedAcl::PublicRead);
+        let object = |acl| Object {
+            key: "index.html".to_string(),
+            bucket: "bucket".to_string(),
+            owner_id: "alice".to_string(),
+            size: 0,
+            last_modified: Local::now(),
+            etag: String::new(),
+            part_sizes: Vec::new(),
+            acl: Acl::canned(acl, "alice", "alice"),
+            tags: Default::default(),
+            metadata: Default::default(),
+            version_id: None,
+            delete_marker: false,
+        };
+        let allowed = |object: &Object| {
+            authorize(
+                &anonymous,
+                Action::GetObject,
+                Some(&bucket),
+                Some(object),
+                Some("index.html"),
+                &HashMap::new(),
+            )
+            .is_ok()
+        };
+
+        assert!(!allowed(&object(CannedAcl::Private)));
+        assert!(allowed(&object(CannedAcl::PublicRead)));
+    }
+}
@@ -0,0 +1,71 @@
+use chrono::{DateTime, Utc};
+
+use crate::entities::error::Error;
+use crate::entities::object::Object;
+
+// the conditions a request sets on the object it reads or replaces. A write
+// only goes through if its body matches `content_md5`, the base64 encoded
+// digest the client expects
+#[derive(Debug, Default)]
+pub struct Conditions {
+    pub content_md5: Option<String>,
+    pub if_match: Option<String>,
+    pub if_none_match: Option<String>,
+    pub if_modified_since: Option<DateTime<Utc>>,
+    pub if_unmodified_since: Option<DateTime<Utc>>,
+}
+
+impl Conditions {
+    // `object` is the current object, if any. Like S3, a passing If-Match
+    // overrides If-Unmodified-Since and a failing If-None-Match overrides
+    // If-Modified-Since, and If-Match on a missing object is NoSuchKey. Dates
+    // only have a precision of seconds
+    pub fn check(&self, object: Option<&Object>, read: bool) -> Result<(), Error> {
+        let modified_after = |since: DateTime<Utc>| {
+            object.is_some_and(|o| o.last_modified.timestamp() > since.timestamp())
+        };
+        let not_modified = match read {
+            true => Error::NotModified,
+            false => Error::PreconditionFailed,
+        };
+
+        match (&self.if_match, self.if_unmodified_since) {
+            (Some(_), _) if object.is_none() => return Err(Error::NoSuchKey),
+            (Some(etags), _) if !etag_matches(etags, object) => {
+                return Err(Error::PreconditionFailed)
+            }
+            (None, Some(since)) if modified_after(since) => return Err(Error::PreconditionFailed),
+            _ => {}
+        }
+
+        match (&self.if_none_match, self.if_modified_since) {
+            (Some(etags), _) if etag_matches(etags, object) => Err(not_modified),
+            (None, Some(since)) if read && object.is_some() && !modified_after(since) => {
+                Err(not_modified)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // a copy fails on every condition that doesn't hold, including the ones
+    // that make a GET return 304
+    pub fn check_copy_source(&self, object: &Object) -> Result<(), Error> {
+        match self.check(Some(object), true) {
+            Err(Error::NotModified) => Err(Error::PreconditionFailed),
+            result => result,
+        }
+    }
+}
+
+// `etags` is a list of quoted, possibly weak, ETags or `*` for any object
+fn etag_matches(etags: &str, object: Option<&Object>) -> bool {
+    let object = match object {
+        Some(object) => object,
+        None => return false,
+    };
+
+    etags.split(',').any(|etag| {
+        let etag = etag.trim();
+        etag == "*" || etag.trim_start_matches("W/").trim_matches('"') == object.etag
+    })
+}
@@ -0,0 +1,31 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Local;
+
+use crate::interactors::storage::Storage;
+
+// applies every bucket's lifecycle rules once per `period`. A failing bucket
+// is reported and retried on the next run without holding up the others
+pub async fn run(storage: Arc<Storage>, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+    loop {
+        interval.tick().await;
+
+        let buckets = match storage.list_all_buckets() {
+            Ok(buckets) => buckets,
+            Err(e) => {
+                eprintln!("lifecycle: failed to list buckets: {}", e);
+                continue;
+            }
+        };
+        for bucket in buckets {
+            if let Err(e) = storage.apply_lifecycle(&bucket, Local::now()).await {
+                eprintln!(
+                    "lifecycle: failed to expire objects in {}: {}",
+                    bucket.name, e
+                );
+            }
+        }
+    }
+}
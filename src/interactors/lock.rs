@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+type LockMap = Arc<std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>>>;
+
+#[derive(Clone, Default)]
+pub struct KeyLocks {
+    locks: LockMap,
+}
+
+pub struct KeyGuard {
+    key: String,
+    locks: LockMap,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl KeyLocks {
+    pub async fn lock(&self, key: &str) -> KeyGuard {
+        let lock = self
+            .locks
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_default()
+            .clone();
+
+        KeyGuard {
+            key: key.to_string(),
+            locks: self.locks.clone(),
+            guard: Some(lock.lock_owned().await),
+        }
+    }
+}
+
+impl Drop for KeyGuard {
+    fn drop(&mut self) {
+        let mut locks = self.locks.lock().unwrap();
+        self.guard.take();
+
+        // waiters clone the entry under the map lock, so a count of one means
+        // nobody else is holding or waiting on this key
+        if locks
+            .get(&self.key)
+            .is_some_and(|l| Arc::strong_count(l) == 1)
+        {
+            locks.remove(&self.key);
+        }
+    }
+}
@@ -1 +1,5 @@
+pub mod authorization;
+pub mod conditions;
+pub mod lifecycle;
+pub mod lock;
 pub mod storage;
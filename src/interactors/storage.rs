@@ -1,122 +1,1883 @@
 use std::clone::Clone;
-use std::collections::HashSet;
-use std::fs;
-use std::fs::File;
-use std::io::prelude::*;
-use std::path::Path;
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
 
-use chrono::Local;
+use chrono::{DateTime, Duration, Local};
+use futures::{stream, Stream, TryStreamExt};
+use hyper::body::Bytes;
+use md5::{Digest, Md5};
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 
 use crate::drivers::db::Db;
-use crate::entities::bucket::Bucket;
-use crate::entities::object::Object;
+use crate::drivers::s3::{base64_encode, hex_decode};
+use crate::entities::acl::{Acl, CannedAcl};
+use crate::entities::bucket::{Bucket, Versioning};
+use crate::entities::error::Error;
+use crate::entities::lifecycle::Lifecycle;
+use crate::entities::object::{Metadata, Object, NULL_VERSION};
+use crate::entities::policy::Policy;
+use crate::entities::upload::{Part, Upload};
 use crate::entities::user::User;
+use crate::interactors::conditions::Conditions;
+use crate::interactors::lock::KeyLocks;
+
+const UPLOADS_DIR: &str = ".uploads";
+const VERSIONS_DIR: &str = ".versions";
+const DIR_SUFFIX: &str = "%";
+// file names are limited to 255 bytes, and directories get DIR_SUFFIX
+const MAX_SEGMENT_LENGTH: usize = 255 - DIR_SUFFIX.len();
+const MAX_KEY_LENGTH: usize = 1024;
+// bumped whenever key_path changes, so files are moved once
+const KEY_LAYOUT: u64 = 1;
+const CHUNK_SIZE: usize = 64 * 1024;
+
+pub type ObjectStream = Pin<Box<dyn Stream<Item = io::Result<Bytes>> + Send>>;
+
+// the part of an object a GET asks for, as sent in a Range header or as the
+// number of a part the object was uploaded in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ObjectRange {
+    From(i64),
+    Between(i64, i64),
+    Suffix(i64),
+    Part(i64),
+}
+
+#[derive(Debug)]
+pub struct ObjectDigest {
+    pub md5: String,
+}
+
+// what a write sets on an object besides its content
+#[derive(Debug, Default)]
+pub struct ObjectOptions {
+    pub acl: Acl,
+    pub tags: BTreeMap<String, String>,
+    pub metadata: Metadata,
+}
+
+// the object a copy reads, what it takes over from it besides the content
+// and the conditions it has to meet
+#[derive(Debug)]
+pub struct CopySource {
+    pub bucket: String,
+    pub key: String,
+    pub version_id: Option<String>,
+    pub copy_metadata: bool,
+    pub copy_tags: bool,
+    pub conditions: Conditions,
+}
+
+#[derive(Debug, Default)]
+pub struct ObjectListing {
+    pub objects: Vec<Object>,
+    pub common_prefixes: Vec<String>,
+    pub next_marker: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct ObjectVersion {
+    pub object: Object,
+    pub is_latest: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct VersionListing {
+    pub versions: Vec<ObjectVersion>,
+    pub common_prefixes: Vec<String>,
+    pub next_marker: Option<(String, String)>,
+}
 
 #[derive(Clone)]
 pub struct Storage {
     base_path: String,
     db: Db,
+    locks: KeyLocks,
 }
 
 impl Storage {
-    pub fn new(base_path: &str) -> Self {
-        Self {
+    pub fn new(base_path: &str) -> Result<Self, Error> {
+        let storage = Self {
             base_path: base_path.to_string(),
-            db: Db::new(&format!("{}/.anbar.db", base_path)),
+            db: Db::new(&format!("{}/.anbar.db", base_path))?,
+            locks: KeyLocks::default(),
+        };
+        if storage.db.get_key_layout()? < KEY_LAYOUT {
+            storage.migrate_key_paths()?;
+            storage.db.set_key_layout(KEY_LAYOUT)?;
         }
+        Ok(storage)
+    }
+
+    // objects used to be stored at their raw key. Their files move to
+    // key_path, unless either path belongs to another stored object
+    fn migrate_key_paths(&self) -> Result<(), Error> {
+        for bucket in self.db.get_buckets() {
+            let bucket = bucket?;
+            let root = Path::new(&self.base_path).join(&bucket.name);
+            let objects = self
+                .db
+                .get_objects_by_prefix(&bucket.name, "", None)
+                .collect::<Result<Vec<Object>, Error>>()?;
+            let paths: HashSet<PathBuf> = objects
+                .iter()
+                .map(|object| self.object_path(&bucket.name, &object.key))
+                .collect();
+
+            for object in &objects {
+                let legacy = match legacy_key_path(&object.key) {
+                    Some(legacy) => root.join(legacy),
+                    None => continue,
+                };
+                let path = self.object_path(&bucket.name, &object.key);
+                if path.exists() || paths.contains(&legacy) || !legacy.is_file() {
+                    continue;
+                }
+
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                std::fs::rename(&legacy, &path)?;
+                for dir in legacy.ancestors().skip(1).take_while(|dir| *dir != root) {
+                    if std::fs::remove_dir(dir).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
     }
 
     pub fn new_user(
-        &mut self,
+        &self,
         id: &str,
         display_name: &str,
         access_key: &str,
         secret_access_key: &str,
-    ) {
+    ) -> Result<(), Error> {
         let user = User {
             id: id.to_string(),
             display_name: display_name.to_string(),
             access_key: access_key.to_string(),
             secret_access_key: secret_access_key.to_string(),
         };
+        if user.is_anonymous() {
+            return Err(Error::InvalidArgument(format!(
+                "The user id {} is reserved.",
+                id
+            )));
+        }
 
-        self.db.create_user(&user);
+        self.db.create_user(&user)
     }
 
-    pub fn find_user(&self, id: &str) -> Option<User> {
-        self.db.get_user_by_access_key(id)
+    pub fn find_user(&self, access_key: &str) -> Result<User, Error> {
+        self.db
+            .get_user_by_access_key(access_key)?
+            .ok_or(Error::InvalidAccessKeyId)
     }
 
-    pub fn create_bucket(&mut self, owner_id: &str, name: &str) {
-        let path = Path::new(&self.base_path).join(name);
+    pub fn find_user_by_id(&self, id: &str) -> Result<User, Error> {
+        self.db
+            .get_user(id)?
+            .ok_or_else(|| Error::InvalidArgument("Invalid id".to_string()))
+    }
 
-        if !path.exists() {
-            fs::create_dir(path).unwrap();
+    pub async fn create_bucket(
+        &self,
+        owner_id: &str,
+        name: &str,
+        location: Option<&str>,
+    ) -> Result<(), Error> {
+        if !Bucket::is_valid_name(name) {
+            return Err(Error::InvalidBucketName);
+        }
+        let taken = |bucket: Bucket| match bucket.owner_id == owner_id {
+            true => Error::BucketAlreadyOwnedByYou,
+            false => Error::BucketAlreadyExists,
+        };
+        if let Some(bucket) = self.db.get_bucket(name)? {
+            return Err(taken(bucket));
         }
 
+        let path = Path::new(&self.base_path).join(name);
+        tokio::fs::create_dir_all(path).await?;
+
         let bucket = Bucket {
             name: name.to_string(),
             owner_id: owner_id.to_string(),
             object_count: 0,
             size: 0,
             creation_date: Local::now(),
+            acl: Acl::canned(CannedAcl::Private, owner_id, owner_id),
+            policy: None,
+            versioning: Versioning::Unversioned,
+            lifecycle: None,
+            location: location.map(|l| l.to_string()),
         };
 
-        self.db.create_bucket(&bucket);
+        // someone else may have created it since it was looked up
+        match self.db.create_bucket(&bucket) {
+            Err(Error::BucketAlreadyExists) => Err(self
+                .db
+                .get_bucket(name)?
+                .map_or(Error::BucketAlreadyExists, taken)),
+            result => result,
+        }
+    }
+
+    pub fn set_bucket_acl(&self, name: &str, acl: Acl) -> Result<Bucket, Error> {
+        self.db
+            .update_bucket(name, |bucket| bucket.acl = acl.clone())
     }
 
-    pub fn list_buckets(&self, owner_id: &str) -> HashSet<Bucket> {
+    pub fn set_bucket_policy(&self, name: &str, policy: Option<Policy>) -> Result<Bucket, Error> {
+        self.db
+            .update_bucket(name, |bucket| bucket.policy = policy.clone())
+    }
+
+    pub fn set_bucket_versioning(
+        &self,
+        name: &str,
+        versioning: Versioning,
+    ) -> Result<Bucket, Error> {
+        self.db
+            .update_bucket(name, |bucket| bucket.versioning = versioning)
+    }
+
+    pub fn set_bucket_lifecycle(
+        &self,
+        name: &str,
+        lifecycle: Option<Lifecycle>,
+    ) -> Result<Bucket, Error> {
+        self.db
+            .update_bucket(name, |bucket| bucket.lifecycle = lifecycle.clone())
+    }
+
+    pub fn find_bucket(&self, name: &str) -> Result<Bucket, Error> {
+        self.db.get_bucket(name)?.ok_or(Error::NoSuchBucket)
+    }
+
+    pub fn list_buckets(&self, owner_id: &str) -> Result<HashSet<Bucket>, Error> {
         self.db.get_buckets_by_user_id(owner_id)
     }
 
-    pub fn list_objects(&self, bucket: &str) -> HashSet<Object> {
-        self.db.get_objects_by_bucket_name(bucket)
+    pub fn list_all_buckets(&self) -> Result<Vec<Bucket>, Error> {
+        self.db.get_buckets().collect()
     }
 
-    pub fn put_object(&mut self, user: &User, bucket: &str, object: &str, body: &[u8]) {
-        let path = Path::new(&self.base_path).join(bucket).join(object);
+    pub fn list_objects(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        delimiter: Option<&str>,
+        marker: Option<&str>,
+        max_keys: usize,
+    ) -> Result<ObjectListing, Error> {
+        self.find_bucket(bucket)?;
 
-        let mut file = File::with_options()
-            .create(true)
-            .truncate(true)
-            .write(true)
-            .open(path)
-            .unwrap();
-        file.write_all(body).unwrap();
-        file.sync_all().unwrap();
+        let mut listing = ObjectListing::default();
+        let mut last_key = None;
+        let mut count = 0;
+
+        for object in self.db.get_objects_by_prefix(bucket, prefix, marker) {
+            let object = object?;
+            let common_prefix = delimiter.filter(|d| !d.is_empty()).and_then(|d| {
+                object.key[prefix.len()..]
+                    .find(d)
+                    .map(|i| object.key[..prefix.len() + i + d.len()].to_string())
+            });
+
+            // keys sharing the previous common prefix were already rolled up
+            if common_prefix.is_some() && common_prefix.as_ref() == listing.common_prefixes.last() {
+                last_key = Some(object.key);
+                continue;
+            }
+
+            if count == max_keys {
+                listing.next_marker = last_key;
+                break;
+            }
+            count += 1;
+            last_key = Some(object.key.to_string());
+
+            match common_prefix {
+                Some(common_prefix) => listing.common_prefixes.push(common_prefix),
+                None => listing.objects.push(object),
+            }
+        }
+
+        Ok(listing)
+    }
+
+    pub async fn put_object<S, E>(
+        &self,
+        user: &User,
+        bucket: &str,
+        object: &str,
+        options: ObjectOptions,
+        conditions: &Conditions,
+        body: S,
+    ) -> Result<Object, Error>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Unpin,
+        E: Into<Error>,
+    {
+        let bucket = self.find_bucket(bucket)?;
+        check_key(object)?;
+
+        let staging = self.staging_path(&bucket.name).await?;
+        let (size, digest) =
+            write_staged(&staging, body, conditions.content_md5.as_deref()).await?;
 
         let obj = Object {
             key: object.to_string(),
+            bucket: bucket.name.to_string(),
+            owner_id: user.id.to_string(),
+            size,
+            last_modified: Local::now(),
+            etag: digest.md5,
+            part_sizes: Vec::new(),
+            acl: options.acl,
+            tags: options.tags,
+            metadata: options.metadata,
+            version_id: None,
+            delete_marker: false,
+        };
+
+        self.commit_object(&bucket, obj, &staging, conditions).await
+    }
+
+    // returns the source version that was read and the copy
+    pub async fn copy_object(
+        &self,
+        user: &User,
+        source: &CopySource,
+        bucket: &str,
+        object: &str,
+        options: ObjectOptions,
+    ) -> Result<(Object, Object), Error> {
+        self.find_bucket(bucket)?;
+        if source.bucket == bucket
+            && source.key == object
+            && source.version_id.is_none()
+            && source.copy_metadata
+        {
+            return Err(Error::InvalidRequest(
+                "This copy request is illegal because it is trying to copy an object to itself without changing the object's metadata.".to_string(),
+            ));
+        }
+
+        let (src, stream) = self.read_copy_source(source, None).await?;
+        let options = ObjectOptions {
+            acl: options.acl,
+            tags: match source.copy_tags {
+                true => src.tags.clone(),
+                false => options.tags,
+            },
+            metadata: match source.copy_metadata {
+                true => src.metadata.clone(),
+                false => options.metadata,
+            },
+        };
+        let copy = self
+            .put_object(
+                user,
+                bucket,
+                object,
+                options,
+                &Conditions::default(),
+                stream,
+            )
+            .await?;
+
+        Ok((src, copy))
+    }
+
+    async fn read_copy_source(
+        &self,
+        source: &CopySource,
+        range: Option<ObjectRange>,
+    ) -> Result<(Object, ObjectStream), Error> {
+        let _guard = self
+            .locks
+            .lock(&object_lock(&source.bucket, &source.key))
+            .await;
+        let (object, is_current) =
+            self.find_version(&source.bucket, &source.key, source.version_id.as_deref())?;
+        source.conditions.check_copy_source(&object)?;
+
+        // unlike a GET, a copy range isn't clamped to the object
+        if let Some(ObjectRange::Between(_, end)) = range {
+            if end >= object.size {
+                return Err(Error::InvalidArgument(format!(
+                    "Range specified is not valid for source object of size: {}",
+                    object.size
+                )));
+            }
+        }
+
+        let (object, _, stream) = self.open_object(object, is_current, range).await?;
+        Ok((object, stream))
+    }
+
+    pub fn list_object_versions(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        delimiter: Option<&str>,
+        key_marker: Option<&str>,
+        version_id_marker: Option<&str>,
+        max_keys: usize,
+    ) -> Result<VersionListing, Error> {
+        self.find_bucket(bucket)?;
+
+        // merge the current versions with the noncurrent ones, a key's current
+        // version sorting before its older ones
+        let mut current = self
+            .db
+            .get_objects_by_prefix(bucket, prefix, None)
+            .peekable();
+        let mut noncurrent = self
+            .db
+            .get_object_versions_by_prefix(bucket, prefix)
+            .peekable();
+        let versions = std::iter::from_fn(|| match (current.peek(), noncurrent.peek()) {
+            (Some(Ok(c)), Some(Ok(n))) if c.key > n.key => noncurrent.next(),
+            (Some(_), _) => current.next(),
+            (None, _) => noncurrent.next(),
+        });
+
+        let mut listing = VersionListing::default();
+        let mut previous_key: Option<String> = None;
+        let mut passed_marker = version_id_marker.is_none();
+        let mut last_version = None;
+        let mut count = 0;
+
+        for version in versions {
+            let version = version?;
+            let version_id = version.version().to_string();
+            let is_latest = previous_key.as_ref() != Some(&version.key);
+            previous_key = Some(version.key.to_string());
+
+            if let Some(key_marker) = key_marker {
+                if version.key.as_str() < key_marker
+                    || (version.key == key_marker && !passed_marker)
+                {
+                    passed_marker |= version_id_marker == Some(version_id.as_str());
+                    continue;
+                }
+                if version.key == key_marker && version_id_marker.is_none() {
+                    continue;
+                }
+            }
+
+            let common_prefix = delimiter.filter(|d| !d.is_empty()).and_then(|d| {
+                version.key[prefix.len()..]
+                    .find(d)
+                    .map(|i| version.key[..prefix.len() + i + d.len()].to_string())
+            });
+
+            if common_prefix.is_some() && common_prefix.as_ref() == listing.common_prefixes.last() {
+                last_version = Some((version.key, version_id));
+                continue;
+            }
+
+            if count == max_keys {
+                listing.next_marker = last_version;
+                break;
+            }
+            count += 1;
+            last_version = Some((version.key.to_string(), version_id));
+
+            match common_prefix {
+                Some(common_prefix) => listing.common_prefixes.push(common_prefix),
+                None => listing.versions.push(ObjectVersion {
+                    object: version,
+                    is_latest,
+                }),
+            }
+        }
+
+        Ok(listing)
+    }
+
+    // the conditions are checked before the range, so a request whose
+    // preconditions fail gets 412 or 304 even if its range is unsatisfiable
+    pub async fn get_object(
+        &self,
+        bucket: &str,
+        object: &str,
+        version_id: Option<&str>,
+        range: Option<ObjectRange>,
+        conditions: &Conditions,
+    ) -> Result<(Object, Option<(i64, i64)>, ObjectStream), Error> {
+        let _guard = self.locks.lock(&object_lock(bucket, object)).await;
+        let (obj, is_current) = self.find_version(bucket, object, version_id)?;
+        conditions.check(Some(&obj), true)?;
+
+        self.open_object(obj, is_current, range).await
+    }
+
+    async fn open_object(
+        &self,
+        obj: Object,
+        is_current: bool,
+        range: Option<ObjectRange>,
+    ) -> Result<(Object, Option<(i64, i64)>, ObjectStream), Error> {
+        let range = range.map(|range| resolve_range(&obj, range)).transpose()?;
+
+        let path = match is_current {
+            true => self.object_path(&obj.bucket, &obj.key),
+            false => self.version_path(&obj),
+        };
+
+        let mut file = match File::open(path).await {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(Error::NoSuchKey),
+            file => file?,
+        };
+
+        match range {
+            Some((start, end)) => {
+                file.seek(io::SeekFrom::Start(start as u64)).await?;
+                let stream = read_stream(file.take((end - start + 1) as u64));
+                Ok((obj, range, stream))
+            }
+            None => Ok((obj, None, read_stream(file))),
+        }
+    }
+
+    pub fn head_object(
+        &self,
+        bucket: &str,
+        object: &str,
+        version_id: Option<&str>,
+    ) -> Result<Object, Error> {
+        Ok(self.find_version(bucket, object, version_id)?.0)
+    }
+
+    // the requested version of an object and whether it's the current one
+    fn find_version(
+        &self,
+        bucket: &str,
+        object: &str,
+        version_id: Option<&str>,
+    ) -> Result<(Object, bool), Error> {
+        self.find_bucket(bucket)?;
+
+        let current = self.db.get_object(bucket, object)?;
+        let version_id = match version_id {
+            Some(version_id) => parse_version_id(version_id),
+            None => return current.map(|c| (c, true)).ok_or(Error::NoSuchKey),
+        };
+
+        match current {
+            Some(current) if current.version_id.as_deref() == version_id => Ok((current, true)),
+            _ => match self
+                .db
+                .get_object_versions(bucket, object)?
+                .into_iter()
+                .find(|v| v.version_id.as_deref() == version_id)
+            {
+                Some(version) if version.delete_marker => Err(Error::MethodNotAllowed),
+                Some(version) => Ok((version, false)),
+                None => Err(Error::NoSuchVersion),
+            },
+        }
+    }
+
+    pub fn set_object_acl(&self, bucket: &str, object: &str, acl: Acl) -> Result<Object, Error> {
+        self.find_bucket(bucket)?;
+
+        self.db
+            .update_object(bucket, object, |obj| obj.acl = acl.clone())
+    }
+
+    pub async fn delete_bucket(&self, bucket: &str) -> Result<(), Error> {
+        let bucket = self.find_bucket(bucket)?;
+
+        if self.db.has_objects(&bucket.name)? {
+            return Err(Error::BucketNotEmpty);
+        }
+
+        let path = Path::new(&self.base_path).join(&bucket.name);
+        tokio::fs::remove_dir_all(path).await?;
+
+        self.db.delete_bucket(&bucket)
+    }
+
+    // returns the delete marker that was created, or the version that was
+    // removed when `version_id` is given
+    pub async fn delete_object(
+        &self,
+        user: &User,
+        bucket: &str,
+        object: &str,
+        version_id: Option<&str>,
+    ) -> Result<Option<Object>, Error> {
+        let bucket = self.find_bucket(bucket)?;
+
+        let _guard = self.locks.lock(&object_lock(&bucket.name, object)).await;
+        match version_id {
+            Some(version_id) => {
+                self.delete_version(&bucket.name, object, parse_version_id(version_id))
+                    .await
+            }
+            None => self.delete_current(user, &bucket, object).await,
+        }
+    }
+
+    // `objects` are pairs of key and version id; the outcome of each is
+    // returned in the same order. Current objects of an unversioned bucket
+    // leave the index in a single batch, the rest are deleted one by one
+    pub async fn delete_objects(
+        &self,
+        user: &User,
+        bucket: &str,
+        objects: &[(String, Option<String>)],
+    ) -> Result<Vec<Result<Option<Object>, Error>>, Error> {
+        let bucket = self.find_bucket(bucket)?;
+
+        // locking in order keeps two overlapping batches from deadlocking
+        let mut keys: Vec<&str> = objects.iter().map(|(key, _)| key.as_str()).collect();
+        keys.sort_unstable();
+        keys.dedup();
+        let mut guards = Vec::with_capacity(keys.len());
+        for key in keys {
+            guards.push(self.locks.lock(&object_lock(&bucket.name, key)).await);
+        }
+
+        // without versioning the index entries go first, in one batch, so a
+        // failure never leaves entries behind for files that are gone
+        let unversioned: Vec<&str> = objects
+            .iter()
+            .filter(|(_, version_id)| version_id.is_none())
+            .filter(|_| bucket.versioning == Versioning::Unversioned)
+            .map(|(key, _)| key.as_str())
+            .collect();
+        let unindexed = self
+            .db
+            .delete_objects(&bucket.name, &unversioned)
+            .map_err(|e| e.to_string());
+
+        let mut results = Vec::with_capacity(objects.len());
+        for (key, version_id) in objects {
+            let result = match version_id {
+                Some(version_id) => {
+                    self.delete_version(&bucket.name, key, parse_version_id(version_id))
+                        .await
+                }
+                None if bucket.versioning == Versioning::Unversioned => match &unindexed {
+                    Ok(()) => self
+                        .remove_object_file(&bucket.name, key)
+                        .await
+                        .map(|_| None),
+                    Err(cause) => Err(Error::InternalError(cause.to_string())),
+                },
+                None => self.delete_current(user, &bucket, key).await,
+            };
+            results.push(result);
+        }
+
+        Ok(results)
+    }
+
+    // the caller must hold the key's lock
+    async fn delete_current(
+        &self,
+        user: &User,
+        bucket: &Bucket,
+        object: &str,
+    ) -> Result<Option<Object>, Error> {
+        // whatever is still current afterwards is an unversioned or null
+        // version, which a delete removes for good
+        self.archive_current(bucket, object).await?;
+        self.remove_current(&bucket.name, object).await?;
+        if bucket.versioning == Versioning::Unversioned {
+            return Ok(None);
+        }
+
+        let marker = Object {
+            key: object.to_string(),
+            bucket: bucket.name.to_string(),
+            owner_id: user.id.to_string(),
+            size: 0,
+            last_modified: Local::now(),
+            etag: String::new(),
+            part_sizes: Vec::new(),
+            acl: Acl::default(),
+            tags: BTreeMap::new(),
+            metadata: Metadata::default(),
+            version_id: self.new_version_id(bucket, object)?,
+            delete_marker: true,
+        };
+        self.db.create_object_version(&marker)?;
+
+        Ok(Some(marker))
+    }
+
+    async fn delete_version(
+        &self,
+        bucket: &str,
+        object: &str,
+        version_id: Option<&str>,
+    ) -> Result<Option<Object>, Error> {
+        let removed = match self.db.get_object(bucket, object)? {
+            Some(current) if current.version_id.as_deref() == version_id => {
+                self.remove_current(bucket, object).await?;
+                Some(current)
+            }
+            _ => match self.db.delete_object_version(bucket, object, version_id)? {
+                Some(version) if !version.delete_marker => {
+                    remove_file(&self.version_path(&version)).await?;
+                    Some(version)
+                }
+                version => version,
+            },
+        };
+
+        // with the latest version gone the one before it becomes current,
+        // unless that one is a delete marker
+        if self.db.get_object(bucket, object)?.is_none() {
+            if let Some(latest) = self.db.get_object_versions(bucket, object)?.first() {
+                if !latest.delete_marker {
+                    move_file(
+                        &self.version_path(latest),
+                        &self.object_path(bucket, object),
+                    )
+                    .await?;
+                    self.db
+                        .delete_object_version(bucket, object, latest.version_id.as_deref())?;
+                    self.db.create_object(latest)?;
+                }
+            }
+        }
+
+        Ok(removed)
+    }
+
+    // makes the staged `object` the current version of its key if the
+    // current one meets `conditions`; the caller must not hold the key's lock
+    async fn commit_object(
+        &self,
+        bucket: &Bucket,
+        mut object: Object,
+        staging: &Path,
+        conditions: &Conditions,
+    ) -> Result<Object, Error> {
+        object.version_id = self.new_version_id(bucket, &object.key)?;
+
+        let _guard = self
+            .locks
+            .lock(&object_lock(&object.bucket, &object.key))
+            .await;
+        let current = self.db.get_object(&object.bucket, &object.key);
+        let checked = current.and_then(|current| conditions.check(current.as_ref(), false));
+        if let Err(e) = match checked {
+            Ok(()) => self.archive_current(bucket, &object.key).await,
+            e => e,
+        } {
+            tokio::fs::remove_file(staging).await.ok();
+            return Err(e);
+        }
+        commit_staged(staging, &self.object_path(&object.bucket, &object.key)).await?;
+        self.db.create_object(&object)?;
+
+        Ok(object)
+    }
+
+    // moves the current version of a key to the noncurrent versions when the
+    // bucket is versioned. Once versioning is suspended the null version is
+    // replaced instead, like in an unversioned bucket
+    async fn archive_current(&self, bucket: &Bucket, object: &str) -> Result<(), Error> {
+        if bucket.versioning == Versioning::Suspended {
+            if let Some(version) = self.db.delete_object_version(&bucket.name, object, None)? {
+                if !version.delete_marker {
+                    remove_file(&self.version_path(&version)).await?;
+                }
+            }
+        }
+
+        let current = match self.db.get_object(&bucket.name, object)? {
+            Some(current)
+                if bucket.versioning == Versioning::Enabled
+                    || (bucket.versioning == Versioning::Suspended
+                        && current.version_id.is_some()) =>
+            {
+                current
+            }
+            _ => return Ok(()),
+        };
+
+        let path = self.version_path(&current);
+        tokio::fs::create_dir_all(
+            Path::new(&self.base_path)
+                .join(&bucket.name)
+                .join(VERSIONS_DIR),
+        )
+        .await?;
+        tokio::fs::rename(self.object_path(&bucket.name, object), path).await?;
+        self.db.create_object_version(&current)?;
+        self.db.delete_object(&bucket.name, object)
+    }
+
+    async fn remove_current(&self, bucket: &str, object: &str) -> Result<(), Error> {
+        self.remove_object_file(bucket, object).await?;
+
+        self.db.delete_object(bucket, object)
+    }
+
+    // also removes the directories of the key that are left empty, deepest
+    // first, so deleted prefixes don't pile up on disk
+    async fn remove_object_file(&self, bucket: &str, object: &str) -> Result<(), Error> {
+        let path = self.object_path(bucket, object);
+        remove_file(&path).await?;
+
+        let root = Path::new(&self.base_path).join(bucket);
+        for dir in path.ancestors().skip(1).take_while(|dir| *dir != root) {
+            if tokio::fs::remove_dir(dir).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn new_version_id(&self, bucket: &Bucket, object: &str) -> Result<Option<String>, Error> {
+        if bucket.versioning != Versioning::Enabled {
+            return Ok(None);
+        }
+
+        let id = self.db.generate_id()?;
+        Ok(Some(format!(
+            "{:x}",
+            Md5::digest(format!("{}/{}/{}", bucket.name, object, id).as_bytes())
+        )))
+    }
+
+    fn object_path(&self, bucket: &str, object: &str) -> PathBuf {
+        Path::new(&self.base_path)
+            .join(bucket)
+            .join(key_path(object))
+    }
+
+    // noncurrent versions live in a flat directory per bucket, named after
+    // the key and version so the null version of a key has a place too
+    fn version_path(&self, object: &Object) -> PathBuf {
+        let name = Md5::digest(format!("{}/{}", object.key, object.version()).as_bytes());
+
+        Path::new(&self.base_path)
+            .join(&object.bucket)
+            .join(VERSIONS_DIR)
+            .join(format!("{:x}", name))
+    }
+
+    fn upload_path(&self, upload: &Upload) -> PathBuf {
+        Path::new(&self.base_path)
+            .join(&upload.bucket)
+            .join(UPLOADS_DIR)
+            .join(&upload.id)
+    }
+
+    pub fn find_upload(&self, upload_id: &str) -> Result<Upload, Error> {
+        self.db.get_upload(upload_id)?.ok_or(Error::NoSuchUpload)
+    }
+
+    pub async fn create_multipart_upload(
+        &self,
+        user: &User,
+        bucket: &str,
+        object: &str,
+        options: ObjectOptions,
+    ) -> Result<Upload, Error> {
+        self.find_bucket(bucket)?;
+        check_key(object)?;
+
+        let id = self.db.generate_id()?;
+        let upload = Upload {
+            id: format!(
+                "{:x}",
+                Md5::digest(format!("{}/{}/{}", bucket, object, id).as_bytes())
+            ),
             bucket: bucket.to_string(),
+            key: object.to_string(),
             owner_id: user.id.to_string(),
-            size: body.len() as i64,
+            initiated: Local::now(),
+            acl: options.acl,
+            tags: options.tags,
+            metadata: options.metadata,
+        };
+
+        tokio::fs::create_dir_all(self.upload_path(&upload)).await?;
+        self.db.create_upload(&upload)?;
+
+        Ok(upload)
+    }
+
+    pub async fn upload_part<S, E>(
+        &self,
+        upload: &Upload,
+        part_number: i64,
+        content_md5: Option<&str>,
+        body: S,
+    ) -> Result<Part, Error>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Unpin,
+        E: Into<Error>,
+    {
+        if !(1..=10000).contains(&part_number) {
+            return Err(Error::InvalidArgument(
+                "Part number must be an integer between 1 and 10000, inclusive.".to_string(),
+            ));
+        }
+
+        let path = self.upload_path(upload).join(part_number.to_string());
+        let staging = self.staging_path(&upload.bucket).await?;
+        let (size, digest) = write_staged(&staging, body, content_md5).await?;
+
+        let part = Part {
+            number: part_number,
+            etag: digest.md5,
+            size,
+            last_modified: Local::now(),
+        };
+
+        let _guard = self
+            .locks
+            .lock(&format!("{}/{}", upload.id, part_number))
+            .await;
+        commit_staged(&staging, &path).await?;
+        self.db.create_part(&upload.id, &part)?;
+
+        Ok(part)
+    }
+
+    // returns the source version that was read and the new part
+    pub async fn upload_part_copy(
+        &self,
+        upload: &Upload,
+        part_number: i64,
+        source: &CopySource,
+        range: Option<ObjectRange>,
+    ) -> Result<(Object, Part), Error> {
+        let (src, stream) = self.read_copy_source(source, range).await?;
+        let part = self.upload_part(upload, part_number, None, stream).await?;
+
+        Ok((src, part))
+    }
+
+    pub async fn complete_multipart_upload(
+        &self,
+        upload: &Upload,
+        parts: &[(i64, String)],
+        conditions: &Conditions,
+    ) -> Result<Object, Error> {
+        let bucket = self.find_bucket(&upload.bucket)?;
+        let uploaded = self.db.get_parts_by_upload_id(&upload.id)?;
+        let upload_path = self.upload_path(upload);
+
+        if parts.is_empty() {
+            return Err(Error::MalformedXML);
+        }
+        if parts.windows(2).any(|w| w[0].0 >= w[1].0) {
+            return Err(Error::InvalidPartOrder);
+        }
+
+        let mut selected = Vec::with_capacity(parts.len());
+        for (number, etag) in parts {
+            let part = uploaded
+                .iter()
+                .find(|p| p.number == *number && p.etag == etag.trim_matches('"'))
+                .ok_or(Error::InvalidPart)?;
+            selected.push(part);
+        }
+
+        let staging = self.staging_path(&upload.bucket).await?;
+        let mut file = File::create(&staging).await?;
+        let mut hasher = Md5::new();
+        let mut size = 0;
+        for part in &selected {
+            let mut part_file = File::open(upload_path.join(part.number.to_string())).await?;
+            size += tokio::io::copy(&mut part_file, &mut file).await? as i64;
+            hasher.update(hex_decode(&part.etag));
+        }
+        file.sync_all().await?;
+
+        let obj = Object {
+            key: upload.key.to_string(),
+            bucket: upload.bucket.to_string(),
+            owner_id: upload.owner_id.to_string(),
+            size,
             last_modified: Local::now(),
+            etag: format!("{:x}-{}", hasher.finalize(), selected.len()),
+            part_sizes: selected.iter().map(|part| part.size).collect(),
+            acl: upload.acl.clone(),
+            tags: upload.tags.clone(),
+            metadata: upload.metadata.clone(),
+            version_id: None,
+            delete_marker: false,
+        };
+        let obj = self
+            .commit_object(&bucket, obj, &staging, conditions)
+            .await?;
+
+        self.abort_multipart_upload(upload).await?;
+
+        Ok(obj)
+    }
+
+    pub async fn abort_multipart_upload(&self, upload: &Upload) -> Result<(), Error> {
+        let path = self.upload_path(upload);
+        match tokio::fs::remove_dir_all(path).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+
+        self.db.delete_upload(&upload.id)
+    }
+
+    // applies the lifecycle rules of `bucket` as of `now`; objects only
+    // expire when they haven't changed since they were found to be expired
+    pub async fn apply_lifecycle(
+        &self,
+        bucket: &Bucket,
+        now: DateTime<Local>,
+    ) -> Result<(), Error> {
+        let lifecycle = match &bucket.lifecycle {
+            Some(lifecycle) => lifecycle,
+            None => return Ok(()),
+        };
+        // a rule too far out to reach a representable date never expires
+        let expired = |since: DateTime<Local>, days: Option<i64>| {
+            days.and_then(Duration::try_days)
+                .and_then(|days| since.checked_add_signed(days))
+                .is_some_and(|at| at <= now)
         };
+        let owner = self.find_user_by_id(&bucket.owner_id)?;
 
-        self.db.create_object(&obj);
+        for rule in &lifecycle.rules {
+            let objects = self
+                .db
+                .get_objects_by_prefix(&bucket.name, &rule.prefix, None)
+                .filter(|object| {
+                    object.as_ref().map_or(true, |o| {
+                        rule.matches(&o.key, &o.tags)
+                            && expired(o.last_modified, rule.expiration_days)
+                    })
+                })
+                .collect::<Result<Vec<Object>, Error>>()?;
+            for object in objects {
+                let _guard = self
+                    .locks
+                    .lock(&object_lock(&bucket.name, &object.key))
+                    .await;
+                let current = self.db.get_object(&bucket.name, &object.key)?;
+                if current.is_some_and(|c| {
+                    c.version_id == object.version_id && c.last_modified == object.last_modified
+                }) {
+                    self.delete_current(&owner, bucket, &object.key).await?;
+                }
+            }
+
+            // a version becomes noncurrent when the next one is created, and
+            // the newest version of a key without a current one is the latest
+            let mut versions = Vec::new();
+            let mut newer: Option<(String, DateTime<Local>)> = None;
+            for version in self
+                .db
+                .get_object_versions_by_prefix(&bucket.name, &rule.prefix)
+            {
+                let version = version?;
+                let since = match newer.take() {
+                    Some((key, since)) if key == version.key => Some(since),
+                    _ => self
+                        .db
+                        .get_object(&bucket.name, &version.key)?
+                        .map(|current| current.last_modified),
+                };
+                newer = Some((version.key.to_string(), version.last_modified));
+
+                if since.is_some_and(|since| expired(since, rule.noncurrent_expiration_days))
+                    && rule.matches(&version.key, &version.tags)
+                {
+                    versions.push(version);
+                }
+            }
+            for version in versions {
+                let _guard = self
+                    .locks
+                    .lock(&object_lock(&bucket.name, &version.key))
+                    .await;
+                self.delete_version(&bucket.name, &version.key, version.version_id.as_deref())
+                    .await?;
+            }
+
+            let uploads = self
+                .db
+                .get_uploads()
+                .filter(|upload| {
+                    upload.as_ref().map_or(true, |u| {
+                        u.bucket == bucket.name
+                            && rule.matches(&u.key, &u.tags)
+                            && expired(u.initiated, rule.abort_incomplete_upload_days)
+                    })
+                })
+                .collect::<Result<Vec<Upload>, Error>>()?;
+            for upload in uploads {
+                self.abort_multipart_upload(&upload).await?;
+            }
+        }
+
+        Ok(())
     }
 
-    pub fn get_object(&self, bucket: &str, object: &str, buf: &mut Vec<u8>) -> Object {
-        let path = Path::new(&self.base_path).join(bucket).join(object);
+    async fn staging_path(&self, bucket: &str) -> Result<PathBuf, Error> {
+        let dir = Path::new(&self.base_path).join(bucket).join(UPLOADS_DIR);
+        tokio::fs::create_dir_all(&dir).await?;
 
-        let mut file = File::open(path).unwrap();
+        // ids are unique, unlike timestamps, so concurrent writes never share a file
+        Ok(dir.join(format!("{}.tmp", self.db.generate_id()?)))
+    }
+}
 
-        file.read_to_end(buf).unwrap();
+fn object_lock(bucket: &str, object: &str) -> String {
+    format!("{}/{}", bucket, object)
+}
 
-        self.db.get_object(bucket, object).unwrap()
+fn check_key(key: &str) -> Result<(), Error> {
+    match key.len() > MAX_KEY_LENGTH {
+        true => Err(Error::KeyTooLongError),
+        false => Ok(()),
     }
+}
 
-    pub fn delete_bucket(&mut self, bucket: &str) {
-        let path = Path::new(&self.base_path).join(bucket);
-        fs::remove_dir_all(path).unwrap();
+// every segment of a key but the last is a directory, named with
+// DIR_SUFFIX so `a` and `a/b` can both be stored
+fn key_path(key: &str) -> PathBuf {
+    let mut segments: Vec<String> = key.split('/').map(encode_segment).collect();
+    let name = segments.pop().unwrap_or_default();
 
-        self.db.delete_bucket(bucket);
+    segments
+        .iter()
+        .map(|dir| format!("{}{}", dir, DIR_SUFFIX))
+        .chain(std::iter::once(name))
+        .collect()
+}
+
+// where a key was stored before key_path, if that was inside its bucket
+fn legacy_key_path(key: &str) -> Option<PathBuf> {
+    let inside = !key.contains('\0')
+        && key
+            .split('/')
+            .all(|segment| !matches!(segment, "" | "." | ".."));
+    Some(PathBuf::from(key)).filter(|_| inside)
+}
+
+// an encoded segment never starts with a dot, so no key reaches `.`, `..` or
+// the bucket's own directories, and never ends in a bare DIR_SUFFIX, except
+// for the empty segment, which is the suffix alone. Segments too long for a
+// file name are replaced by their digest behind `%~`, which no escape starts with
+fn encode_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for (i, c) in segment.char_indices() {
+        match c {
+            '%' | '\0' => encoded.push_str(&format!("%{:02X}", c as u8)),
+            '.' if i == 0 => encoded.push_str("%2E"),
+            c => encoded.push(c),
+        }
+    }
+
+    match encoded.len() {
+        0 => DIR_SUFFIX.to_string(),
+        len if len > MAX_SEGMENT_LENGTH => {
+            format!("%~{:x}", Md5::digest(segment.as_bytes()))
+        }
+        _ => encoded,
+    }
+}
+
+// the first and last byte of `range` within `object`
+fn resolve_range(object: &Object, range: ObjectRange) -> Result<(i64, i64), Error> {
+    let size = object.size;
+
+    match range {
+        ObjectRange::Part(number) => {
+            let sizes = match object.part_sizes.is_empty() {
+                true => vec![size],
+                false => object.part_sizes.clone(),
+            };
+            if number < 1 || number as usize > sizes.len() {
+                return Err(Error::InvalidPartNumber);
+            }
+            let start = sizes[..number as usize - 1].iter().sum::<i64>();
+            Ok((start, start + sizes[number as usize - 1] - 1))
+        }
+        ObjectRange::From(start) if start < size => Ok((start, size - 1)),
+        ObjectRange::Between(start, end) if start < size => Ok((start, end.min(size - 1))),
+        ObjectRange::Suffix(length) if length > 0 && size > 0 => {
+            Ok(((size - length).max(0), size - 1))
+        }
+        _ => Err(Error::InvalidRange),
+    }
+}
+
+fn parse_version_id(version_id: &str) -> Option<&str> {
+    Some(version_id).filter(|v| *v != NULL_VERSION)
+}
+
+async fn remove_file(path: &Path) -> Result<(), Error> {
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+// streams the body into a staging file first so a failed write never
+// clobbers the existing file; the caller commits it with `commit_staged`.
+// `content_md5` is the base64 encoded digest the client expects
+async fn write_staged<S, E>(
+    staging: &Path,
+    body: S,
+    content_md5: Option<&str>,
+) -> Result<(i64, ObjectDigest), Error>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: Into<Error>,
+{
+    let written = match write_file(staging, body).await {
+        Ok((_, digest))
+            if content_md5.is_some_and(|md5| md5 != base64_encode(&hex_decode(&digest.md5))) =>
+        {
+            Err(Error::BadDigest)
+        }
+        written => written,
+    };
+    if written.is_err() {
+        tokio::fs::remove_file(staging).await.ok();
+    }
+    written
+}
+
+async fn commit_staged(staging: &Path, path: &Path) -> Result<(), Error> {
+    if let Err(e) = move_file(staging, path).await {
+        tokio::fs::remove_file(staging).await.ok();
+        return Err(e);
+    }
+    Ok(())
+}
+
+// creates the directories `path` needs first. Deleting another key may remove
+// them again while they're empty, in which case the move is retried
+async fn move_file(from: &Path, path: &Path) -> Result<(), Error> {
+    loop {
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        match tokio::fs::rename(from, path).await {
+            Err(e)
+                if e.kind() == io::ErrorKind::NotFound
+                    && tokio::fs::metadata(from).await.is_ok() =>
+            {
+                continue
+            }
+            result => return Ok(result?),
+        }
+    }
+}
+
+async fn write_file<S, E>(path: &Path, mut body: S) -> Result<(i64, ObjectDigest), Error>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: Into<Error>,
+{
+    let mut file = File::create(path).await?;
+
+    let mut md5 = Md5::new();
+    let mut size = 0;
+    while let Some(chunk) = body.try_next().await.map_err(|e| e.into())? {
+        md5.update(&chunk);
+        file.write_all(&chunk).await?;
+        size += chunk.len() as i64;
+    }
+    file.sync_all().await?;
+
+    Ok((
+        size,
+        ObjectDigest {
+            md5: format!("{:x}", md5.finalize()),
+        },
+    ))
+}
+
+fn read_stream<R>(reader: R) -> ObjectStream
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    Box::pin(stream::try_unfold(reader, |mut reader| async move {
+        let mut buf = vec![0; CHUNK_SIZE];
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buf.truncate(n);
+        Ok(Some((Bytes::from(buf), reader)))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::lifecycle::Rule;
+
+    async fn storage(name: &str) -> (Storage, User) {
+        let path =
+            std::env::temp_dir().join(format!("anbar-storage-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&path).ok();
+        std::fs::create_dir_all(&path).unwrap();
+
+        let storage = Storage::new(path.to_str().unwrap()).unwrap();
+        storage
+            .new_user("alice", "Alice", "ALICEKEY", "secret")
+            .unwrap();
+        storage
+            .create_bucket("alice", "bucket", None)
+            .await
+            .unwrap();
+        let user = storage.find_user("ALICEKEY").unwrap();
+        (storage, user)
+    }
+
+    fn body(data: &str) -> impl Stream<Item = Result<Bytes, Error>> + Unpin {
+        stream::iter(vec![Ok(Bytes::from(data.to_string()))])
+    }
+
+    async fn upload(storage: &Storage, user: &User, key: &str) -> Upload {
+        storage
+            .create_multipart_upload(user, "bucket", key, ObjectOptions::default())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn deleted_bucket_takes_its_uploads() {
+        let (storage, user) = storage("delete-bucket").await;
+        let upload = upload(&storage, &user, "big").await;
+        storage
+            .upload_part(&upload, 1, None, body("part"))
+            .await
+            .unwrap();
+
+        storage.delete_bucket("bucket").await.unwrap();
+        storage
+            .create_bucket("alice", "bucket", None)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            storage.find_upload(&upload.id),
+            Err(Error::NoSuchUpload)
+        ));
+        assert!(storage
+            .db
+            .get_parts_by_upload_id(&upload.id)
+            .unwrap()
+            .is_empty());
+    }
+
+    async fn read(storage: &Storage, key: &str) -> String {
+        let (_, _, stream) = storage
+            .get_object("bucket", key, None, None, &Conditions::default())
+            .await
+            .unwrap();
+        let data: Vec<u8> = stream.map_ok(|b| b.to_vec()).try_concat().await.unwrap();
+        String::from_utf8(data).unwrap()
     }
 
-    pub fn delete_object(&mut self, bucket: &str, object: &str) {
-        let path = Path::new(&self.base_path).join(bucket).join(object);
-        fs::remove_file(path).unwrap();
+    #[tokio::test]
+    async fn multipart_upload_joins_the_selected_parts() {
+        let (storage, user) = storage("multipart").await;
+        let upload = upload(&storage, &user, "big").await;
+        let mut etags = Vec::new();
+        for (number, data) in [(2, "world"), (1, "hello "), (3, "unused")] {
+            let part = storage
+                .upload_part(&upload, number, None, body(data))
+                .await
+                .unwrap();
+            etags.push((number, format!("\"{}\"", part.etag)));
+        }
+        etags.sort();
+
+        let object = storage
+            .complete_multipart_upload(&upload, &etags[..2], &Conditions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(read(&storage, "big").await, "hello world");
+        assert_eq!(object.size, 11);
+        assert_eq!(object.part_sizes, vec![6, 5]);
+        assert!(object.etag.ends_with("-2"));
+        assert!(matches!(
+            storage.find_upload(&upload.id),
+            Err(Error::NoSuchUpload)
+        ));
+        assert!(!storage.upload_path(&upload).exists());
+    }
+
+    #[tokio::test]
+    async fn multipart_upload_rejects_bad_part_lists() {
+        let (storage, user) = storage("multipart-parts").await;
+        let upload = upload(&storage, &user, "big").await;
+        let one = storage
+            .upload_part(&upload, 1, None, body("a"))
+            .await
+            .unwrap();
+        let two = storage
+            .upload_part(&upload, 2, None, body("b"))
+            .await
+            .unwrap();
+        let complete = |parts: Vec<(i64, String)>| {
+            let storage = storage.clone();
+            let upload = upload.clone();
+            async move {
+                storage
+                    .complete_multipart_upload(&upload, &parts, &Conditions::default())
+                    .await
+            }
+        };
+
+        let reversed = vec![(2, two.etag.to_string()), (1, one.etag.to_string())];
+        assert!(matches!(
+            complete(reversed).await,
+            Err(Error::InvalidPartOrder)
+        ));
+        let wrong_etag = vec![(1, two.etag.to_string())];
+        assert!(matches!(
+            complete(wrong_etag).await,
+            Err(Error::InvalidPart)
+        ));
+        let missing = vec![(1, one.etag.to_string()), (5, two.etag.to_string())];
+        assert!(matches!(complete(missing).await, Err(Error::InvalidPart)));
+        assert!(matches!(
+            complete(Vec::new()).await,
+            Err(Error::MalformedXML)
+        ));
+        assert!(matches!(
+            storage.upload_part(&upload, 0, None, body("x")).await,
+            Err(Error::InvalidArgument(_))
+        ));
+
+        // a part uploaded again replaces the earlier one
+        let again = storage
+            .upload_part(&upload, 1, None, body("c"))
+            .await
+            .unwrap();
+        complete(vec![(1, again.etag), (2, two.etag)])
+            .await
+            .unwrap();
+        assert_eq!(read(&storage, "big").await, "cb");
+    }
+
+    #[tokio::test]
+    async fn aborted_upload_leaves_nothing_behind() {
+        let (storage, user) = storage("multipart-abort").await;
+        let upload = upload(&storage, &user, "big").await;
+        storage
+            .upload_part(&upload, 1, None, body("a"))
+            .await
+            .unwrap();
+
+        storage.abort_multipart_upload(&upload).await.unwrap();
+
+        assert!(matches!(
+            storage.find_upload(&upload.id),
+            Err(Error::NoSuchUpload)
+        ));
+        assert!(!storage.upload_path(&upload).exists());
+        assert!(matches!(
+            storage.head_object("bucket", "big", None),
+            Err(Error::NoSuchKey)
+        ));
+    }
+
+    async fn put(storage: &Storage, user: &User, key: &str, data: &str) -> Object {
+        storage
+            .put_object(
+                user,
+                "bucket",
+                key,
+                ObjectOptions::default(),
+                &Conditions::default(),
+                body(data),
+            )
+            .await
+            .unwrap()
+    }
+
+    fn keys(listing: &ObjectListing) -> Vec<&str> {
+        listing.objects.iter().map(|o| o.key.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn listing_rolls_keys_up_to_the_delimiter() {
+        let (storage, user) = storage("list-delimiter").await;
+        for key in ["a/1", "a/2", "a/b/3", "ab", "b", "c/4"] {
+            put(&storage, &user, key, key).await;
+        }
+
+        let listing = storage
+            .list_objects("bucket", "", Some("/"), None, 1000)
+            .unwrap();
+        assert_eq!(keys(&listing), ["ab", "b"]);
+        assert_eq!(listing.common_prefixes, ["a/", "c/"]);
+        assert_eq!(listing.next_marker, None);
+
+        let listing = storage
+            .list_objects("bucket", "a/", Some("/"), None, 1000)
+            .unwrap();
+        assert_eq!(keys(&listing), ["a/1", "a/2"]);
+        assert_eq!(listing.common_prefixes, ["a/b/"]);
+
+        let listing = storage
+            .list_objects("bucket", "a", None, None, 1000)
+            .unwrap();
+        assert_eq!(keys(&listing), ["a/1", "a/2", "a/b/3", "ab"]);
+        assert!(listing.common_prefixes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn listing_pages_resume_after_the_marker() {
+        let (storage, user) = storage("list-marker").await;
+        for key in ["a/1", "a/2", "b", "c/3", "d"] {
+            put(&storage, &user, key, key).await;
+        }
+
+        let mut pages = Vec::new();
+        let mut marker = None;
+        loop {
+            let listing = storage
+                .list_objects("bucket", "", Some("/"), marker.as_deref(), 2)
+                .unwrap();
+            let mut page: Vec<String> = listing.common_prefixes.clone();
+            page.extend(listing.objects.iter().map(|o| o.key.to_string()));
+            page.sort();
+            pages.push(page);
+            marker = listing.next_marker;
+            if marker.is_none() {
+                break;
+            }
+        }
+
+        assert_eq!(pages, [vec!["a/", "b"], vec!["c/", "d"]]);
+        assert!(matches!(
+            storage.list_objects("missing", "", None, None, 1000),
+            Err(Error::NoSuchBucket)
+        ));
+    }
+
+    #[tokio::test]
+    async fn head_object_describes_without_reading() {
+        let (storage, user) = storage("head").await;
+        let object = put(&storage, &user, "notes", "hello").await;
+
+        let head = storage.head_object("bucket", "notes", None).unwrap();
+        assert_eq!(head.size, 5);
+        assert_eq!(head.etag, object.etag);
+        assert_eq!(head.last_modified, object.last_modified);
+
+        assert!(matches!(
+            storage.head_object("bucket", "other", None),
+            Err(Error::NoSuchKey)
+        ));
+        assert!(matches!(
+            storage.head_object("missing", "notes", None),
+            Err(Error::NoSuchBucket)
+        ));
+    }
+
+    #[tokio::test]
+    async fn large_bodies_stream_in_chunks() {
+        let (storage, user) = storage("stream").await;
+        let data: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+        let chunks: Vec<Result<Bytes, Error>> = data
+            .chunks(50_000)
+            .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+            .collect();
+        let object = storage
+            .put_object(
+                &user,
+                "bucket",
+                "large",
+                ObjectOptions::default(),
+                &Conditions::default(),
+                stream::iter(chunks),
+            )
+            .await
+            .unwrap();
+        assert_eq!(object.size, 200_000);
+        assert_eq!(object.etag, format!("{:x}", Md5::digest(&data)));
+
+        let (_, _, stream) = storage
+            .get_object("bucket", "large", None, None, &Conditions::default())
+            .await
+            .unwrap();
+        let read: Vec<Bytes> = stream.try_collect().await.unwrap();
+        assert!(read.len() > 1);
+        assert!(read.iter().all(|chunk| chunk.len() <= CHUNK_SIZE));
+        assert_eq!(read.concat(), data);
+
+        let range = Some(ObjectRange::Between(60_000, 139_999));
+        let (_, byte_range, stream) = storage
+            .get_object("bucket", "large", None, range, &Conditions::default())
+            .await
+            .unwrap();
+        let read: Vec<u8> = stream.map_ok(|b| b.to_vec()).try_concat().await.unwrap();
+        assert_eq!(byte_range, Some((60_000, 139_999)));
+        assert_eq!(read, &data[60_000..140_000]);
+    }
+
+    #[tokio::test]
+    async fn failed_body_leaves_no_object() {
+        let (storage, user) = storage("stream-error").await;
+        let chunks = vec![Ok(Bytes::from("partial")), Err(Error::IncompleteBody)];
+        let put = storage
+            .put_object(
+                &user,
+                "bucket",
+                "broken",
+                ObjectOptions::default(),
+                &Conditions::default(),
+                stream::iter(chunks),
+            )
+            .await;
+
+        assert!(matches!(put, Err(Error::IncompleteBody)));
+        assert!(matches!(
+            storage.head_object("bucket", "broken", None),
+            Err(Error::NoSuchKey)
+        ));
+        assert!(!storage.object_path("bucket", "broken").exists());
+    }
+
+    #[tokio::test]
+    async fn baseline_index_is_migrated_on_open() {
+        let path =
+            std::env::temp_dir().join(format!("anbar-storage-legacy-{}", std::process::id()));
+        std::fs::remove_dir_all(&path).ok();
+        std::fs::create_dir_all(&path).unwrap();
+
+        // the layout before objects were indexed one per key
+        {
+            let db = sled::open(path.join(".anbar.db")).unwrap();
+            let bucket = serde_json::json!({
+                "name": "bucket",
+                "owner_id": "alice",
+                "object_count": 0,
+                "size": 0,
+                "creation_date": "2020-01-01T00:00:00+00:00",
+            });
+            let objects = serde_json::json!([
+                {"key": "b", "bucket": "bucket", "owner_id": "alice", "size": 2, "last_modified": "2020-01-02T00:00:00+00:00"},
+                {"key": "a", "bucket": "bucket", "owner_id": "alice", "size": 1, "last_modified": "2020-01-02T00:00:00+00:00"},
+            ]);
+            db.open_tree("bucket_name_to_bucket")
+                .unwrap()
+                .insert("bucket", serde_json::to_vec(&bucket).unwrap())
+                .unwrap();
+            db.open_tree("bucket_name_to_objects")
+                .unwrap()
+                .insert("bucket", serde_json::to_vec(&objects).unwrap())
+                .unwrap();
+            db.flush().unwrap();
+        }
+
+        let storage = Storage::new(path.to_str().unwrap()).unwrap();
+        let listing = storage
+            .list_objects("bucket", "", None, None, 1000)
+            .unwrap();
+        assert_eq!(keys(&listing), ["a", "b"]);
+        assert_eq!(storage.head_object("bucket", "b", None).unwrap().size, 2);
+        drop(storage);
+
+        // the legacy tree is gone, so opening again doesn't migrate twice
+        let storage = Storage::new(path.to_str().unwrap()).unwrap();
+        let listing = storage
+            .list_objects("bucket", "", None, None, 1000)
+            .unwrap();
+        assert_eq!(keys(&listing), ["a", "b"]);
+    }
+
+    #[test]
+    fn unopenable_index_is_an_error() {
+        let path =
+            std::env::temp_dir().join(format!("anbar-storage-broken-{}", std::process::id()));
+        std::fs::remove_dir_all(&path).ok();
+        std::fs::create_dir_all(&path).unwrap();
+        std::fs::write(path.join(".anbar.db"), "not a directory").unwrap();
+
+        assert!(matches!(
+            Storage::new(path.to_str().unwrap()),
+            Err(Error::InternalError(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_puts_never_mix_bodies() {
+        let (storage, user) = storage("concurrent").await;
+        let bodies: Vec<String> = (0..16)
+            .map(|i| format!("{:x}", i).repeat(100_000))
+            .collect();
+
+        let puts = bodies.iter().enumerate().map(|(i, data)| {
+            let storage = storage.clone();
+            let user = user.clone();
+            let chunks: Vec<Bytes> = data
+                .as_bytes()
+                .chunks(10_000)
+                .map(Bytes::copy_from_slice)
+                .collect();
+            tokio::spawn(async move {
+                for key in ["shared".to_string(), format!("own-{}", i)] {
+                    storage
+                        .put_object(
+                            &user,
+                            "bucket",
+                            &key,
+                            ObjectOptions::default(),
+                            &Conditions::default(),
+                            stream::iter(chunks.clone().into_iter().map(Ok::<_, Error>)),
+                        )
+                        .await
+                        .unwrap();
+                }
+            })
+        });
+        futures::future::try_join_all(puts).await.unwrap();
+
+        assert!(bodies.contains(&read(&storage, "shared").await));
+        for (i, data) in bodies.iter().enumerate() {
+            assert_eq!(&read(&storage, &format!("own-{}", i)).await, data);
+        }
+        let object = storage.head_object("bucket", "shared", None).unwrap();
+        assert_eq!(
+            object.etag,
+            format!(
+                "{:x}",
+                Md5::digest(read(&storage, "shared").await.as_bytes())
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn uploads_of_the_same_key_get_their_own_ids() {
+        let (storage, user) = storage("upload-ids").await;
+        let first = upload(&storage, &user, "big").await;
+        let second = upload(&storage, &user, "big").await;
+
+        assert_ne!(first.id, second.id);
+        assert!(storage.upload_path(&first).exists());
+        assert!(storage.upload_path(&second).exists());
+    }
+
+    #[tokio::test]
+    async fn delete_objects_reports_each_key() {
+        let (storage, user) = storage("delete-objects").await;
+        for key in ["a", "b/c", "stuck"] {
+            put(&storage, &user, key, key).await;
+        }
+        // a file that can't be unlinked fails its own key only
+        let stuck = storage.object_path("bucket", "stuck");
+        std::fs::remove_file(&stuck).unwrap();
+        std::fs::create_dir_all(stuck.join("child")).unwrap();
+
+        let names = ["a", "b/c", "missing", "stuck"];
+        let objects: Vec<(String, Option<String>)> =
+            names.iter().map(|key| (key.to_string(), None)).collect();
+        let results = storage
+            .delete_objects(&user, "bucket", &objects)
+            .await
+            .unwrap();
+
+        assert_eq!(results.len(), 4);
+        assert!(results[..3].iter().all(|r| matches!(r, Ok(None))));
+        assert!(matches!(results[3], Err(Error::InternalError(_))));
+        let listing = storage
+            .list_objects("bucket", "", None, None, 1000)
+            .unwrap();
+        assert!(keys(&listing).is_empty());
+        assert!(!storage.object_path("bucket", "a").exists());
+        assert!(!storage
+            .object_path("bucket", "b/c")
+            .parent()
+            .unwrap()
+            .exists());
+        assert!(matches!(
+            storage.delete_objects(&user, "missing", &objects).await,
+            Err(Error::NoSuchBucket)
+        ));
+    }
+
+    #[tokio::test]
+    async fn files_at_their_raw_key_move_to_the_key_path() {
+        let (storage, user) = storage("legacy-paths").await;
+        let keys = [".hidden", "100%", "dir/file", "plain"];
+        for key in keys {
+            put(&storage, &user, key, key).await;
+        }
+
+        // lay the files out the way they were stored before key_path
+        let root = Path::new(&storage.base_path).join("bucket");
+        for key in keys {
+            std::fs::create_dir_all(root.join(key).parent().unwrap()).unwrap();
+            std::fs::rename(storage.object_path("bucket", key), root.join(key)).unwrap();
+        }
+        std::fs::remove_dir(root.join("dir%")).unwrap();
+        storage.db.set_key_layout(0).unwrap();
+        let base_path = storage.base_path.to_string();
+        drop(storage);
+
+        let storage = Storage::new(&base_path).unwrap();
+        for key in keys {
+            assert_eq!(read(&storage, key).await, key);
+        }
+        assert!(!root.join(".hidden").exists());
+        assert!(!root.join("100%").exists());
+        assert!(!root.join("dir").exists());
+        assert_eq!(storage.db.get_key_layout().unwrap(), KEY_LAYOUT);
+
+        // a key that now maps to the old directory's name is free to use
+        put(&storage, &user, "dir", "dir").await;
+        assert_eq!(read(&storage, "dir").await, "dir");
+    }
+
+    #[tokio::test]
+    async fn lifecycle_days_past_the_calendar_never_expire() {
+        let (storage, user) = storage("lifecycle-overflow").await;
+        put(&storage, &user, "old", "old").await;
+        let upload = upload(&storage, &user, "big").await;
+
+        let rules = [100_000_000, i64::MAX]
+            .iter()
+            .map(|days| Rule {
+                id: None,
+                enabled: true,
+                prefix: String::new(),
+                tags: BTreeMap::new(),
+                expiration_days: Some(*days),
+                noncurrent_expiration_days: Some(*days),
+                abort_incomplete_upload_days: Some(*days),
+            })
+            .collect();
+        let bucket = storage
+            .set_bucket_lifecycle("bucket", Some(Lifecycle { rules }))
+            .unwrap();
+        storage
+            .apply_lifecycle(&bucket, Local::now() + Duration::days(365))
+            .await
+            .unwrap();
 
-        self.db.delete_object(bucket, object);
+        assert_eq!(read(&storage, "old").await, "old");
+        assert!(storage.upload_path(&upload).exists());
     }
 }
@@ -1,5 +1,3 @@
-#![feature(with_options)]
-
 pub mod adapters;
 pub mod drivers;
 pub mod entities;
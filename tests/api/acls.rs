@@ -0,0 +1,92 @@
+use hyper::StatusCode;
+
+use crate::common::{app, body, request, send, ALICE, BOB};
+
+#[tokio::test]
+async fn object_acls_grant_access_per_object() {
+    let app = app("acl").await;
+    let mut req = request(Some(ALICE), "PUT", "/alice-bucket/logo", "png");
+    req.headers_mut()
+        .insert("x-amz-acl", "public-read".parse().unwrap());
+    app.clone().handle(req).await.unwrap();
+    send(&app, Some(ALICE), "PUT", "/alice-bucket/notes", "private").await;
+
+    for user in [None, Some(BOB)] {
+        let response = send(&app, user, "GET", "/alice-bucket/logo", "").await;
+        assert_eq!(body(response).await, "png");
+        let response = send(&app, user, "GET", "/alice-bucket/notes", "").await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = send(&app, user, "GET", "/alice-bucket/logo?acl", "").await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    let grant_bob = r#"<AccessControlPolicy>
+        <Owner><ID>alice</ID></Owner>
+        <AccessControlList>
+            <Grant>
+                <Grantee xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:type="CanonicalUser"><ID>bob</ID></Grantee>
+                <Permission>READ</Permission>
+            </Grant>
+        </AccessControlList>
+    </AccessControlPolicy>"#;
+    let response = send(
+        &app,
+        Some(ALICE),
+        "PUT",
+        "/alice-bucket/notes?acl",
+        grant_bob,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send(&app, Some(BOB), "GET", "/alice-bucket/notes", "").await;
+    assert_eq!(body(response).await, "private");
+    let response = send(&app, None, "GET", "/alice-bucket/notes", "").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let acl = body(send(&app, Some(ALICE), "GET", "/alice-bucket/notes?acl", "").await).await;
+    assert!(acl.contains("<Owner><DisplayName>Alice</DisplayName><ID>alice</ID></Owner>"));
+    assert!(acl.contains(
+        "<ID>bob</ID><DisplayName>Bob</DisplayName></Grantee><Permission>READ</Permission>"
+    ));
+
+    for policy in [
+        "<AccessControlPolicy><Grant>",
+        grant_bob.replace("<ID>bob", "<ID>carol").as_str(),
+    ] {
+        let response = send(&app, Some(ALICE), "PUT", "/alice-bucket/notes?acl", policy).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
+
+#[tokio::test]
+async fn bucket_acl_grants_writes_to_other_tenants() {
+    let app = app("bucket-acl").await;
+    let mut req = request(Some(ALICE), "PUT", "/alice-bucket?acl", "");
+    req.headers_mut()
+        .insert("x-amz-acl", "public-read-write".parse().unwrap());
+    assert_eq!(
+        app.clone().handle(req).await.unwrap().status(),
+        StatusCode::OK
+    );
+
+    let acl = body(send(&app, Some(ALICE), "GET", "/alice-bucket?acl", "").await).await;
+    assert!(acl.contains("AllUsers</URI></Grantee><Permission>WRITE</Permission>"));
+
+    let mut req = request(Some(BOB), "PUT", "/alice-bucket/drop", "from bob");
+    req.headers_mut()
+        .insert("x-amz-acl", "bucket-owner-read".parse().unwrap());
+    assert_eq!(
+        app.clone().handle(req).await.unwrap().status(),
+        StatusCode::OK
+    );
+
+    let acl = body(send(&app, Some(BOB), "GET", "/alice-bucket/drop?acl", "").await).await;
+    assert!(acl.contains("<ID>bob</ID>"));
+    assert!(acl.contains(
+        "<ID>alice</ID><DisplayName>Alice</DisplayName></Grantee><Permission>READ</Permission>"
+    ));
+
+    let response = send(&app, Some(BOB), "PUT", "/alice-bucket?acl", "").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
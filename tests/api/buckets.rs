@@ -0,0 +1,48 @@
+use hyper::StatusCode;
+
+use crate::common::{app, body, send, ALICE, BOB};
+
+#[tokio::test]
+async fn bucket_names_and_locations_are_validated() {
+    let app = app("bucket-names").await;
+
+    for name in [
+        "ab",
+        "Upper",
+        ".anbar.db",
+        "a..b",
+        "192.168.1.1",
+        "-dash",
+        "xn--abc",
+    ] {
+        let response = send(&app, Some(ALICE), "PUT", &format!("/{}", name), "").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", name);
+        assert!(
+            body(response).await.contains("InvalidBucketName"),
+            "{}",
+            name
+        );
+    }
+
+    let response = send(&app, Some(ALICE), "PUT", "/alice-bucket", "").await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert!(body(response).await.contains("BucketAlreadyOwnedByYou"));
+    let response = send(&app, Some(BOB), "PUT", "/alice-bucket", "").await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert!(body(response)
+        .await
+        .contains("<Code>BucketAlreadyExists</Code>"));
+
+    let configuration = "<CreateBucketConfiguration><LocationConstraint>eu-west-1</LocationConstraint></CreateBucketConfiguration>";
+    let response = send(&app, Some(BOB), "PUT", "/bob-bucket", configuration).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = send(&app, Some(BOB), "GET", "/bob-bucket?location", "").await;
+    assert!(body(response)
+        .await
+        .contains(">eu-west-1</LocationConstraint>"));
+    let response = send(&app, Some(ALICE), "GET", "/alice-bucket?location", "").await;
+    assert!(body(response).await.contains("\"></LocationConstraint>"));
+
+    let response = send(&app, Some(BOB), "PUT", "/bob-other", "<Nope/>").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
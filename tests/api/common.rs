@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use hyper::{Body, Request, Response};
+
+use anbar::drivers::s3::Auth;
+use anbar::drivers::web_server::App;
+use anbar::interactors::storage::Storage;
+
+pub const ALICE: (&str, &str) = ("ALICEKEY", "alice-secret");
+pub const BOB: (&str, &str) = ("BOBKEY", "bob-secret");
+
+pub async fn app(name: &str) -> App {
+    let path = std::env::temp_dir().join(format!("anbar-{}-{}", name, std::process::id()));
+    std::fs::remove_dir_all(&path).ok();
+    std::fs::create_dir_all(&path).unwrap();
+
+    let storage = Storage::new(path.to_str().unwrap()).unwrap();
+    storage
+        .new_user("alice", "Alice", ALICE.0, ALICE.1)
+        .unwrap();
+    storage.new_user("bob", "Bob", BOB.0, BOB.1).unwrap();
+    storage
+        .create_bucket("alice", "alice-bucket", None)
+        .await
+        .unwrap();
+
+    App {
+        storage: Arc::new(storage),
+    }
+}
+
+pub fn request(user: Option<(&str, &str)>, method: &str, uri: &str, body: &str) -> Request<Body> {
+    let now = Utc::now();
+    let mut req = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("host", "localhost")
+        .header("x-amz-date", now.format("%Y%m%dT%H%M%SZ").to_string())
+        .header("x-amz-content-sha256", "UNSIGNED-PAYLOAD")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+
+    if let Some((access_key, secret_access_key)) = user {
+        let auth = Auth {
+            access_key: access_key.to_string(),
+            date: now.format("%Y%m%d").to_string(),
+            region: "us-east-1".to_string(),
+            signature: String::new(),
+            signed_headers: vec![
+                "host".to_string(),
+                "x-amz-content-sha256".to_string(),
+                "x-amz-date".to_string(),
+            ],
+            presigned: None,
+        };
+        let header = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}/us-east-1/s3/aws4_request,SignedHeaders={},Signature={}",
+            access_key,
+            auth.date,
+            auth.signed_headers.join(";"),
+            auth.sign(secret_access_key, &req).unwrap()
+        );
+        req.headers_mut()
+            .insert("authorization", header.parse().unwrap());
+    }
+    req
+}
+
+pub async fn send(
+    app: &App,
+    user: Option<(&str, &str)>,
+    method: &str,
+    uri: &str,
+    body: &str,
+) -> Response<Body> {
+    app.clone()
+        .handle(request(user, method, uri, body))
+        .await
+        .unwrap()
+}
+
+pub async fn body(response: Response<Body>) -> String {
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    String::from_utf8_lossy(&bytes).to_string()
+}
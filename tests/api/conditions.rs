@@ -0,0 +1,117 @@
+use hyper::StatusCode;
+
+use crate::common::{app, body, request, send, ALICE};
+
+#[tokio::test]
+async fn conditional_requests_compare_etags_and_dates() {
+    let app = app("conditions").await;
+    let put = |body: &str, condition: (&'static str, &str)| {
+        let mut req = request(Some(ALICE), "PUT", "/alice-bucket/lock", body);
+        req.headers_mut()
+            .insert(condition.0, condition.1.parse().unwrap());
+        app.clone().handle(req)
+    };
+
+    let response = put("first", ("if-none-match", "*")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    let response = put("second", ("if-none-match", "*")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    let response = put("third", ("if-match", "\"0000\"")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+    for (name, value, status) in [
+        ("if-none-match", etag.as_str(), StatusCode::NOT_MODIFIED),
+        ("if-none-match", "\"0000\"", StatusCode::OK),
+        ("if-match", etag.as_str(), StatusCode::OK),
+        ("if-match", "\"0000\"", StatusCode::PRECONDITION_FAILED),
+        (
+            "if-modified-since",
+            "Sat, 01 Jan 2000 00:00:00 GMT",
+            StatusCode::OK,
+        ),
+        (
+            "if-modified-since",
+            "Fri, 01 Jan 2100 00:00:00 GMT",
+            StatusCode::NOT_MODIFIED,
+        ),
+        (
+            "if-unmodified-since",
+            "Sat, 01 Jan 2000 00:00:00 GMT",
+            StatusCode::PRECONDITION_FAILED,
+        ),
+    ] {
+        let mut req = request(Some(ALICE), "GET", "/alice-bucket/lock", "");
+        req.headers_mut().insert(name, value.parse().unwrap());
+        let response = app.clone().handle(req).await.unwrap();
+        assert_eq!(response.status(), status, "{}: {}", name, value);
+    }
+
+    let response = send(&app, Some(ALICE), "GET", "/alice-bucket/lock", "").await;
+    assert_eq!(body(response).await, "first");
+}
+
+#[tokio::test]
+async fn preconditions_are_checked_before_the_range() {
+    let app = app("conditions-range").await;
+    let response = send(&app, Some(ALICE), "PUT", "/alice-bucket/ten", "0123456789").await;
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+
+    for (condition, status) in [
+        (None, StatusCode::RANGE_NOT_SATISFIABLE),
+        (
+            Some(("if-match", "\"0000\"")),
+            StatusCode::PRECONDITION_FAILED,
+        ),
+        (
+            Some(("if-none-match", etag.as_str())),
+            StatusCode::NOT_MODIFIED,
+        ),
+    ] {
+        let mut req = request(Some(ALICE), "GET", "/alice-bucket/ten", "");
+        req.headers_mut()
+            .insert("range", "bytes=20-30".parse().unwrap());
+        if let Some((name, value)) = condition {
+            req.headers_mut().insert(name, value.parse().unwrap());
+        }
+        let response = app.clone().handle(req).await.unwrap();
+        assert_eq!(response.status(), status, "{:?}", condition);
+    }
+}
+
+#[tokio::test]
+async fn not_modified_identifies_the_object() {
+    let app = app("conditions-304").await;
+    let response = send(&app, Some(ALICE), "PUT", "/alice-bucket/page", "hello").await;
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    let response = send(&app, Some(ALICE), "HEAD", "/alice-bucket/page", "").await;
+    let last_modified = response.headers()["last-modified"].clone();
+
+    for method in ["GET", "HEAD"] {
+        let mut req = request(Some(ALICE), method, "/alice-bucket/page", "");
+        req.headers_mut()
+            .insert("if-none-match", etag.parse().unwrap());
+        let response = app.clone().handle(req).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED, "{}", method);
+        assert_eq!(response.headers()["etag"], etag.as_str());
+        assert_eq!(response.headers()["last-modified"], last_modified);
+        assert_eq!(body(response).await, "");
+    }
+}
+
+#[tokio::test]
+async fn if_match_on_a_missing_key_is_no_such_key() {
+    let app = app("conditions-missing").await;
+
+    for method in ["GET", "PUT"] {
+        let mut req = request(Some(ALICE), method, "/alice-bucket/missing", "data");
+        req.headers_mut().insert("if-match", "*".parse().unwrap());
+        let response = app.clone().handle(req).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", method);
+        assert!(body(response).await.contains("NoSuchKey"));
+    }
+    let response = send(&app, Some(ALICE), "HEAD", "/alice-bucket/missing", "").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,118 @@
+use hyper::StatusCode;
+
+use crate::common::{app, body, request, send, ALICE, BOB};
+
+#[tokio::test]
+async fn copies_read_the_source_on_the_server() {
+    let app = app("copy").await;
+    let copy = |user, uri: &str, headers: &[(&'static str, &str)]| {
+        let mut req = request(Some(user), "PUT", uri, "");
+        req.headers_mut()
+            .insert("x-amz-copy-source", "/alice-bucket/digits".parse().unwrap());
+        for (name, value) in headers {
+            req.headers_mut().insert(*name, value.parse().unwrap());
+        }
+        app.clone().handle(req)
+    };
+    let mut req = request(Some(ALICE), "PUT", "/alice-bucket/digits", "0123456789");
+    req.headers_mut()
+        .insert("x-amz-meta-origin", "alice".parse().unwrap());
+    app.clone().handle(req).await.unwrap();
+    send(&app, Some(ALICE), "PUT", "/alice-copies", "").await;
+    send(&app, Some(BOB), "PUT", "/bob-bucket", "").await;
+
+    let response = copy(ALICE, "/alice-copies/digits", &[]).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(body(response).await.contains("<CopyObjectResult>"));
+    let response = send(&app, Some(ALICE), "GET", "/alice-copies/digits", "").await;
+    assert_eq!(response.headers()["x-amz-meta-origin"], "alice");
+    assert_eq!(body(response).await, "0123456789");
+
+    let headers = [
+        ("x-amz-metadata-directive", "REPLACE"),
+        ("x-amz-meta-origin", "copy"),
+    ];
+    copy(ALICE, "/alice-copies/digits", &headers).await.unwrap();
+    let response = send(&app, Some(ALICE), "HEAD", "/alice-copies/digits", "").await;
+    assert_eq!(response.headers()["x-amz-meta-origin"], "copy");
+
+    let headers = [("x-amz-copy-source-if-match", "\"0000\"")];
+    let response = copy(ALICE, "/alice-copies/other", &headers).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    let response = copy(ALICE, "/alice-bucket/digits", &[]).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = copy(BOB, "/bob-bucket/digits", &[]).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = send(&app, Some(ALICE), "POST", "/alice-copies/part?uploads", "").await;
+    let xml = body(response).await;
+    let upload_id = xml
+        .split("<UploadId>")
+        .nth(1)
+        .and_then(|rest| rest.split("</UploadId>").next())
+        .unwrap()
+        .to_string();
+    let uri = format!("/alice-copies/part?partNumber=1&uploadId={}", upload_id);
+    let headers = [("x-amz-copy-source-range", "bytes=2-4")];
+    let response = copy(ALICE, &uri, &headers).await.unwrap();
+    let xml = body(response).await;
+    assert!(xml.contains("<CopyPartResult>"));
+    let etag = xml
+        .split("<ETag>")
+        .nth(1)
+        .and_then(|rest| rest.split("</ETag>").next())
+        .unwrap();
+    let complete = format!(
+        "<CompleteMultipartUpload><Part><PartNumber>1</PartNumber><ETag>{}</ETag></Part></CompleteMultipartUpload>",
+        etag
+    );
+    let uri = format!("/alice-copies/part?uploadId={}", upload_id);
+    send(&app, Some(ALICE), "POST", &uri, &complete).await;
+    let response = send(&app, Some(ALICE), "GET", "/alice-copies/part", "").await;
+    assert_eq!(body(response).await, "234");
+}
+
+#[tokio::test]
+async fn copy_ranges_must_lie_inside_the_source() {
+    let app = app("copy-range").await;
+    send(
+        &app,
+        Some(ALICE),
+        "PUT",
+        "/alice-bucket/digits",
+        "0123456789",
+    )
+    .await;
+    let response = send(&app, Some(ALICE), "POST", "/alice-bucket/part?uploads", "").await;
+    let xml = body(response).await;
+    let upload_id = xml
+        .split("<UploadId>")
+        .nth(1)
+        .and_then(|rest| rest.split("</UploadId>").next())
+        .unwrap()
+        .to_string();
+    let uri = format!("/alice-bucket/part?partNumber=1&uploadId={}", upload_id);
+
+    for (range, status) in [
+        ("bytes=0-9", StatusCode::OK),
+        ("bytes=5-10", StatusCode::BAD_REQUEST),
+        ("bytes=20-30", StatusCode::BAD_REQUEST),
+        ("bytes=4-2", StatusCode::BAD_REQUEST),
+        ("bytes=2-", StatusCode::BAD_REQUEST),
+    ] {
+        let mut req = request(Some(ALICE), "PUT", &uri, "");
+        req.headers_mut()
+            .insert("x-amz-copy-source", "/alice-bucket/digits".parse().unwrap());
+        req.headers_mut()
+            .insert("x-amz-copy-source-range", range.parse().unwrap());
+        let response = app.clone().handle(req).await.unwrap();
+        assert_eq!(response.status(), status, "{}", range);
+        if status == StatusCode::BAD_REQUEST {
+            assert!(
+                body(response).await.contains("InvalidArgument"),
+                "{}",
+                range
+            );
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use hyper::StatusCode;
+
+use crate::common::{app, body, request, send, ALICE, BOB};
+
+#[tokio::test]
+async fn owner_has_full_access() {
+    let app = app("owner").await;
+
+    for (method, uri, status) in [
+        ("PUT", "/alice-bucket/report", StatusCode::OK),
+        ("GET", "/alice-bucket/report", StatusCode::OK),
+        ("HEAD", "/alice-bucket/report", StatusCode::OK),
+        ("GET", "/alice-bucket", StatusCode::OK),
+        ("HEAD", "/alice-bucket", StatusCode::OK),
+        ("DELETE", "/alice-bucket/report", StatusCode::NO_CONTENT),
+        ("DELETE", "/alice-bucket", StatusCode::NO_CONTENT),
+    ] {
+        let response = send(&app, Some(ALICE), method, uri, "secret data").await;
+        assert_eq!(response.status(), status, "{} {}", method, uri);
+    }
+}
+
+#[tokio::test]
+async fn other_tenant_is_denied() {
+    let app = app("cross").await;
+    send(
+        &app,
+        Some(ALICE),
+        "PUT",
+        "/alice-bucket/report",
+        "secret data",
+    )
+    .await;
+
+    for (method, uri) in [
+        ("GET", "/alice-bucket/report"),
+        ("HEAD", "/alice-bucket/report"),
+        ("PUT", "/alice-bucket/report"),
+        ("DELETE", "/alice-bucket/report"),
+        ("GET", "/alice-bucket"),
+        ("GET", "/alice-bucket?list-type=2"),
+        ("HEAD", "/alice-bucket"),
+        ("PUT", "/alice-bucket?acl"),
+        ("DELETE", "/alice-bucket"),
+        ("POST", "/alice-bucket/report?uploads"),
+    ] {
+        let response = send(&app, Some(BOB), method, uri, "").await;
+        assert_eq!(
+            response.status(),
+            StatusCode::FORBIDDEN,
+            "{} {}",
+            method,
+            uri
+        );
+    }
+
+    let response = send(&app, Some(ALICE), "GET", "/alice-bucket/report", "").await;
+    assert_eq!(body(response).await, "secret data");
+}
+
+#[tokio::test]
+async fn other_tenant_cannot_touch_uploads() {
+    let app = app("uploads").await;
+    let response = send(&app, Some(ALICE), "POST", "/alice-bucket/big?uploads", "").await;
+    let xml = body(response).await;
+    let upload_id = &xml[xml.find("<UploadId>").unwrap() + 10..xml.find("</UploadId>").unwrap()];
+
+    for (method, uri) in [
+        (
+            "PUT",
+            format!("/alice-bucket/big?partNumber=1&uploadId={}", upload_id),
+        ),
+        ("POST", format!("/alice-bucket/big?uploadId={}", upload_id)),
+        (
+            "DELETE",
+            format!("/alice-bucket/big?uploadId={}", upload_id),
+        ),
+    ] {
+        let response = send(&app, Some(BOB), method, &uri, "part").await;
+        assert_eq!(
+            response.status(),
+            StatusCode::FORBIDDEN,
+            "{} {}",
+            method,
+            uri
+        );
+    }
+
+    let uri = format!("/alice-bucket/big?partNumber=1&uploadId={}", upload_id);
+    let response = send(&app, Some(ALICE), "PUT", &uri, "part").await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn bucket_listing_is_per_tenant() {
+    let app = app("listing").await;
+    send(&app, Some(BOB), "PUT", "/bob-bucket", "").await;
+
+    let alice = body(send(&app, Some(ALICE), "GET", "/", "").await).await;
+    let bob = body(send(&app, Some(BOB), "GET", "/", "").await).await;
+
+    assert!(alice.contains("alice-bucket") && !alice.contains("bob-bucket"));
+    assert!(bob.contains("bob-bucket") && !bob.contains("alice-bucket"));
+}
+
+#[tokio::test]
+async fn public_bucket_is_read_only_for_others() {
+    let app = app("public").await;
+    send(&app, Some(ALICE), "PUT", "/alice-bucket/index.html", "hi").await;
+
+    let response = send(&app, None, "GET", "/alice-bucket/index.html", "").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let mut req = request(Some(ALICE), "PUT", "/alice-bucket?acl", "");
+    req.headers_mut()
+        .insert("x-amz-acl", "public-read".parse().unwrap());
+    let response = app.clone().handle(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // the bucket ACL covers listing it; each object keeps its own ACL
+    for user in [None, Some(BOB)] {
+        let response = send(&app, user, "GET", "/alice-bucket/index.html", "").await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = send(&app, user, "GET", "/alice-bucket", "").await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let mut req = request(Some(ALICE), "PUT", "/alice-bucket/index.html?acl", "");
+    req.headers_mut()
+        .insert("x-amz-acl", "public-read".parse().unwrap());
+    let response = app.clone().handle(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    for user in [None, Some(BOB)] {
+        let response = send(&app, user, "GET", "/alice-bucket/index.html", "").await;
+        assert_eq!(body(response).await, "hi");
+
+        for (method, uri) in [
+            ("PUT", "/alice-bucket/index.html"),
+            ("DELETE", "/alice-bucket/index.html"),
+            ("PUT", "/alice-bucket?acl"),
+            ("DELETE", "/alice-bucket"),
+        ] {
+            let response = send(&app, user, method, uri, "defaced").await;
+            assert_eq!(
+                response.status(),
+                StatusCode::FORBIDDEN,
+                "{} {}",
+                method,
+                uri
+            );
+        }
+    }
+
+    let response = send(&app, None, "GET", "/", "").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn public_bucket_policy_serves_every_object() {
+    let app = app("public-policy").await;
+    let mut req = request(Some(ALICE), "PUT", "/alice-bucket?acl", "");
+    req.headers_mut()
+        .insert("x-amz-acl", "public-read".parse().unwrap());
+    app.clone().handle(req).await.unwrap();
+    let policy = r#"{
+        "Version": "2012-10-17",
+        "Statement": [{
+            "Effect": "Allow",
+            "Principal": "*",
+            "Action": "s3:GetObject",
+            "Resource": "arn:aws:s3:::alice-bucket/*"
+        }]
+    }"#;
+    let response = send(&app, Some(ALICE), "PUT", "/alice-bucket?policy", policy).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    // objects written after the policy need no ACL of their own
+    send(
+        &app,
+        Some(ALICE),
+        "PUT",
+        "/alice-bucket/css/site.css",
+        "body",
+    )
+    .await;
+    for user in [None, Some(BOB)] {
+        let response = send(&app, user, "GET", "/alice-bucket/css/site.css", "").await;
+        assert_eq!(body(response).await, "body");
+        let response = send(&app, user, "GET", "/alice-bucket", "").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send(&app, user, "PUT", "/alice-bucket/css/site.css", "x").await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
+
+#[tokio::test]
+async fn unknown_or_forged_credentials_are_rejected() {
+    let app = app("forged").await;
+
+    let response = send(&app, Some(("NOBODY", "x")), "GET", "/alice-bucket", "").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(body(response).await.contains("InvalidAccessKeyId"));
+
+    let response = send(&app, Some((ALICE.0, BOB.1)), "GET", "/alice-bucket", "").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(body(response).await.contains("SignatureDoesNotMatch"));
+}
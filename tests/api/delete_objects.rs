@@ -0,0 +1,127 @@
+use hyper::StatusCode;
+
+use crate::common::{app, body, send, ALICE, BOB};
+
+#[tokio::test]
+async fn delete_objects_reports_each_key() {
+    let app = app("multi-delete").await;
+    for key in ["a", "b", "c"] {
+        send(
+            &app,
+            Some(ALICE),
+            "PUT",
+            &format!("/alice-bucket/{}", key),
+            "x",
+        )
+        .await;
+    }
+    let delete = |quiet: bool, keys: &[&str]| {
+        format!(
+            "<Delete><Quiet>{}</Quiet>{}</Delete>",
+            quiet,
+            keys.iter()
+                .map(|key| format!("<Object><Key>{}</Key></Object>", key))
+                .collect::<String>()
+        )
+    };
+
+    let response = send(
+        &app,
+        Some(BOB),
+        "POST",
+        "/alice-bucket?delete",
+        &delete(false, &["a"]),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(body(response)
+        .await
+        .contains("<Error><Key>a</Key><Code>AccessDenied</Code>"));
+
+    let response = send(
+        &app,
+        Some(ALICE),
+        "POST",
+        "/alice-bucket?delete",
+        &delete(false, &["a", "b", "missing"]),
+    )
+    .await;
+    let xml = body(response).await;
+    for key in ["a", "b", "missing"] {
+        assert!(xml.contains(&format!("<Deleted><Key>{}</Key></Deleted>", key)));
+    }
+    let response = send(
+        &app,
+        Some(ALICE),
+        "POST",
+        "/alice-bucket?delete",
+        &delete(true, &["c"]),
+    )
+    .await;
+    assert_eq!(body(response).await, "<DeleteResult></DeleteResult>");
+
+    let listing = body(send(&app, Some(ALICE), "GET", "/alice-bucket", "").await).await;
+    assert!(!listing.contains("<Contents>"));
+    let response = send(
+        &app,
+        Some(ALICE),
+        "POST",
+        "/alice-bucket?delete",
+        "<Delete/>",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = send(
+        &app,
+        Some(ALICE),
+        "POST",
+        "/alice-bucket?delete",
+        &delete(false, &[""]),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(body(response).await.contains("<Code>MalformedXML</Code>"));
+}
+
+#[tokio::test]
+async fn delete_objects_escapes_keys() {
+    let app = app("multi-delete-escape").await;
+    send(&app, Some(ALICE), "PUT", "/alice-bucket/a%26b%3Cc", "x").await;
+    let delete = "<Delete><Object><Key>a&amp;b&lt;c</Key></Object></Delete>";
+
+    let response = send(&app, Some(BOB), "POST", "/alice-bucket?delete", delete).await;
+    let xml = body(response).await;
+    assert!(xml.contains("<Error><Key>a&amp;b&lt;c</Key>"), "{}", xml);
+
+    let response = send(&app, Some(ALICE), "POST", "/alice-bucket?delete", delete).await;
+    let xml = body(response).await;
+    assert!(xml.contains("<Deleted><Key>a&amp;b&lt;c</Key>"), "{}", xml);
+    let response = send(&app, Some(ALICE), "GET", "/alice-bucket/a%26b%3Cc", "").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn unknown_requests_are_not_allowed() {
+    let app = app("unknown-request").await;
+    send(&app, Some(ALICE), "PUT", "/alice-bucket/a", "x").await;
+    let delete = "<Delete><Object><Key>a</Key></Object></Delete>";
+
+    for (method, uri) in [
+        ("POST", "/alice-bucket"),
+        ("POST", "/"),
+        ("DELETE", "/"),
+        ("PATCH", "/alice-bucket/a"),
+    ] {
+        let response = send(&app, Some(ALICE), method, uri, delete).await;
+        assert_eq!(
+            response.status(),
+            StatusCode::METHOD_NOT_ALLOWED,
+            "{} {}",
+            method,
+            uri
+        );
+        assert!(body(response).await.contains("MethodNotAllowed"));
+    }
+    let response = send(&app, Some(ALICE), "GET", "/alice-bucket/a", "").await;
+    assert_eq!(body(response).await, "x");
+}
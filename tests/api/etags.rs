@@ -0,0 +1,29 @@
+use hyper::StatusCode;
+
+use crate::common::{app, body, request, send, ALICE};
+
+#[tokio::test]
+async fn etags_are_stored_and_content_md5_is_checked() {
+    let app = app("etag").await;
+    // MD5 of "hello"
+    let etag = "\"5d41402abc4b2a76b9719d911017c592\"";
+
+    let mut req = request(Some(ALICE), "PUT", "/alice-bucket/greeting", "hello");
+    req.headers_mut()
+        .insert("content-md5", "XUFAKrxLKna5cZ2REBfFkg==".parse().unwrap());
+    let response = app.clone().handle(req).await.unwrap();
+    assert_eq!(response.headers()["etag"], etag);
+
+    let mut req = request(Some(ALICE), "PUT", "/alice-bucket/greeting", "jello");
+    req.headers_mut()
+        .insert("content-md5", "XUFAKrxLKna5cZ2REBfFkg==".parse().unwrap());
+    let response = app.clone().handle(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(body(response).await.contains("<Code>BadDigest</Code>"));
+
+    let response = send(&app, Some(ALICE), "GET", "/alice-bucket/greeting", "").await;
+    assert_eq!(response.headers()["etag"], etag);
+    assert_eq!(body(response).await, "hello");
+    let listing = body(send(&app, Some(ALICE), "GET", "/alice-bucket", "").await).await;
+    assert!(listing.contains(&format!("<ETag>{}</ETag>", etag)));
+}
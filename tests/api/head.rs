@@ -0,0 +1,27 @@
+use hyper::StatusCode;
+
+use crate::common::{app, body, send, ALICE, BOB};
+
+#[tokio::test]
+async fn head_returns_headers_without_a_body() {
+    let app = app("head").await;
+    send(&app, Some(ALICE), "PUT", "/alice-bucket/notes", "hello").await;
+
+    let response = send(&app, Some(ALICE), "HEAD", "/alice-bucket/notes", "").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-length"], "5");
+    assert!(response.headers().contains_key("etag"));
+    assert!(response.headers().contains_key("last-modified"));
+    assert_eq!(body(response).await, "");
+
+    for (user, uri, status) in [
+        (ALICE, "/alice-bucket/other", StatusCode::NOT_FOUND),
+        (ALICE, "/alice-bucket", StatusCode::OK),
+        (ALICE, "/missing-bucket", StatusCode::NOT_FOUND),
+        (BOB, "/alice-bucket", StatusCode::FORBIDDEN),
+        (BOB, "/alice-bucket/notes", StatusCode::FORBIDDEN),
+    ] {
+        let response = send(&app, Some(user), "HEAD", uri, "").await;
+        assert_eq!(response.status(), status, "{}", uri);
+    }
+}
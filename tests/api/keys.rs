@@ -0,0 +1,117 @@
+use hyper::StatusCode;
+
+use crate::common::{app, body, send, ALICE};
+
+#[tokio::test]
+async fn nested_keys_stay_inside_the_bucket() {
+    let app = app("nested").await;
+    let root = std::env::temp_dir().join(format!("anbar-nested-{}", std::process::id()));
+    let keys = [
+        "a",
+        "a/b",
+        "a/b/c.txt",
+        "a/../../outside",
+        ".uploads/x",
+        "dir/",
+    ];
+
+    for key in keys {
+        let uri = format!("/alice-bucket/{}", key);
+        let response = send(&app, Some(ALICE), "PUT", &uri, key).await;
+        assert_eq!(response.status(), StatusCode::OK, "{}", key);
+    }
+    for key in keys {
+        let uri = format!("/alice-bucket/{}", key);
+        let response = send(&app, Some(ALICE), "GET", &uri, "").await;
+        assert_eq!(body(response).await, key);
+    }
+    assert!(!root.join("outside").exists());
+
+    for key in keys {
+        let uri = format!("/alice-bucket/{}", key);
+        send(&app, Some(ALICE), "DELETE", &uri, "").await;
+    }
+    let left: Vec<_> = std::fs::read_dir(root.join("alice-bucket"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .filter(|name| !name.to_string_lossy().starts_with('.'))
+        .collect();
+    assert!(left.is_empty(), "{:?}", left);
+}
+
+#[tokio::test]
+async fn long_keys_are_stored_or_rejected() {
+    let app = app("long-keys").await;
+    let keys = [
+        "a".repeat(300),
+        format!("{}/{}", "d".repeat(300), "e".repeat(300)),
+        "%".repeat(300),
+        "%".repeat(1024),
+    ];
+
+    for key in &keys {
+        let uri = format!("/alice-bucket/{}", key.replace('%', "%25"));
+        let response = send(&app, Some(ALICE), "PUT", &uri, key).await;
+        assert_eq!(response.status(), StatusCode::OK, "{}", key.len());
+        let response = send(&app, Some(ALICE), "GET", &uri, "").await;
+        assert_eq!(&body(response).await, key);
+    }
+    let listing = body(send(&app, Some(ALICE), "GET", "/alice-bucket", "").await).await;
+    for key in &keys {
+        assert!(listing.contains(&format!("<Key>{}</Key>", key)));
+    }
+    for key in &keys {
+        let uri = format!("/alice-bucket/{}", key.replace('%', "%25"));
+        let response = send(&app, Some(ALICE), "DELETE", &uri, "").await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+
+    let uri = format!("/alice-bucket/{}", "a".repeat(1025));
+    let response = send(&app, Some(ALICE), "PUT", &uri, "x").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(body(response).await.contains("KeyTooLongError"));
+    let uri = format!("{}?uploads", uri);
+    let response = send(&app, Some(ALICE), "POST", &uri, "").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn multipart_results_escape_keys() {
+    let app = app("multipart-keys").await;
+    let xml = body(
+        send(
+            &app,
+            Some(ALICE),
+            "POST",
+            "/alice-bucket/a%26b%3Cc?uploads",
+            "",
+        )
+        .await,
+    )
+    .await;
+    assert!(xml.contains("<Key>a&amp;b&lt;c</Key>"));
+    let upload_id = xml
+        .split("<UploadId>")
+        .nth(1)
+        .and_then(|rest| rest.split("</UploadId>").next())
+        .unwrap()
+        .to_string();
+
+    let uri = format!(
+        "/alice-bucket/a%26b%3Cc?partNumber=1&uploadId={}",
+        upload_id
+    );
+    let response = send(&app, Some(ALICE), "PUT", &uri, "part").await;
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    let complete = format!(
+        "<CompleteMultipartUpload><Part><PartNumber>1</PartNumber><ETag>{}</ETag></Part></CompleteMultipartUpload>",
+        etag
+    );
+    let uri = format!("/alice-bucket/a%26b%3Cc?uploadId={}", upload_id);
+    let xml = body(send(&app, Some(ALICE), "POST", &uri, &complete).await).await;
+    assert!(xml.contains("<Location>/alice-bucket/a&amp;b&lt;c</Location>"));
+    assert!(xml.contains("<Key>a&amp;b&lt;c</Key>"));
+
+    let response = send(&app, Some(ALICE), "GET", "/alice-bucket/a%26b%3Cc", "").await;
+    assert_eq!(body(response).await, "part");
+}
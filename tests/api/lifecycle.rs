@@ -0,0 +1,90 @@
+use chrono::{Duration, Local};
+use hyper::StatusCode;
+
+use crate::common::{app, body, send, ALICE, BOB};
+
+#[tokio::test]
+async fn lifecycle_rules_expire_matching_objects() {
+    let app = app("lifecycle").await;
+    let lifecycle = "<LifecycleConfiguration><Rule><ID>logs</ID><Filter><Prefix>logs-</Prefix></Filter><Status>Enabled</Status><Expiration><Days>30</Days></Expiration><AbortIncompleteMultipartUpload><DaysAfterInitiation>7</DaysAfterInitiation></AbortIncompleteMultipartUpload></Rule></LifecycleConfiguration>";
+    let response = send(&app, Some(BOB), "PUT", "/alice-bucket?lifecycle", lifecycle).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = send(
+        &app,
+        Some(ALICE),
+        "PUT",
+        "/alice-bucket?lifecycle",
+        lifecycle,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = send(
+        &app,
+        Some(ALICE),
+        "PUT",
+        "/alice-bucket?lifecycle",
+        "<Rule>",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let configuration =
+        body(send(&app, Some(ALICE), "GET", "/alice-bucket?lifecycle", "").await).await;
+    assert!(configuration.contains("<Filter><Prefix>logs-</Prefix></Filter>"));
+
+    send(&app, Some(ALICE), "PUT", "/alice-bucket/logs-1", "old").await;
+    send(&app, Some(ALICE), "PUT", "/alice-bucket/report", "keep").await;
+    let upload = body(
+        send(
+            &app,
+            Some(ALICE),
+            "POST",
+            "/alice-bucket/logs-2?uploads",
+            "",
+        )
+        .await,
+    )
+    .await;
+    let upload_id = upload
+        .split("<UploadId>")
+        .nth(1)
+        .unwrap()
+        .split('<')
+        .next()
+        .unwrap();
+
+    let bucket = app.storage.find_bucket("alice-bucket").unwrap();
+    app.storage
+        .apply_lifecycle(&bucket, Local::now() + Duration::days(31))
+        .await
+        .unwrap();
+
+    let response = send(&app, Some(ALICE), "GET", "/alice-bucket/logs-1", "").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = send(&app, Some(ALICE), "GET", "/alice-bucket/report", "").await;
+    assert_eq!(body(response).await, "keep");
+    let uri = format!("/alice-bucket/logs-2?uploadId={}", upload_id);
+    let response = send(&app, Some(ALICE), "DELETE", &uri, "").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = send(&app, Some(ALICE), "DELETE", "/alice-bucket?lifecycle", "").await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = send(&app, Some(ALICE), "GET", "/alice-bucket?lifecycle", "").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn lifecycle_days_past_the_calendar_are_malformed() {
+    let app = app("lifecycle-days").await;
+    let lifecycle = "<LifecycleConfiguration><Rule><Filter><Prefix></Prefix></Filter><Status>Enabled</Status><Expiration><Days>100000000</Days></Expiration></Rule></LifecycleConfiguration>";
+    let response = send(
+        &app,
+        Some(ALICE),
+        "PUT",
+        "/alice-bucket?lifecycle",
+        lifecycle,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(body(response).await.contains("<Code>MalformedXML</Code>"));
+}
@@ -0,0 +1,52 @@
+use hyper::StatusCode;
+
+use crate::common::{app, body, send, ALICE};
+
+#[tokio::test]
+async fn listed_keys_are_escaped() {
+    let app = app("listing-escape").await;
+    send(&app, Some(ALICE), "PUT", "/alice-bucket/a%26b%3Cc", "x").await;
+
+    for uri in [
+        "/alice-bucket?prefix=a%26b",
+        "/alice-bucket?list-type=2&prefix=a%26b",
+    ] {
+        let response = send(&app, Some(ALICE), "GET", uri, "").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let xml = body(response).await;
+        assert!(xml.contains("<Key>a&amp;b&lt;c</Key>"), "{}", xml);
+        assert!(xml.contains("<Prefix>a&amp;b</Prefix>"), "{}", xml);
+    }
+}
+
+#[tokio::test]
+async fn continuation_tokens_page_through_keys() {
+    let app = app("listing-pages").await;
+    for key in ["a/1", "a/2", "b", "c"] {
+        let uri = format!("/alice-bucket/{}", key);
+        send(&app, Some(ALICE), "PUT", &uri, key).await;
+    }
+
+    let mut pages = Vec::new();
+    let mut uri = "/alice-bucket?list-type=2&delimiter=/&max-keys=2".to_string();
+    loop {
+        let xml = body(send(&app, Some(ALICE), "GET", &uri, "").await).await;
+        pages.push(xml.contains("<IsTruncated>true</IsTruncated>"));
+        match xml.split("<NextContinuationToken>").nth(1) {
+            Some(rest) => {
+                let token = rest.split('<').next().unwrap();
+                assert!(xml.contains("<KeyCount>2</KeyCount>"), "{}", xml);
+                uri = format!(
+                    "/alice-bucket?list-type=2&delimiter=/&max-keys=2&continuation-token={}",
+                    token
+                );
+            }
+            None => {
+                assert!(xml.contains("<Key>c</Key>"), "{}", xml);
+                assert!(!xml.contains("<Key>b</Key>"), "{}", xml);
+                break;
+            }
+        }
+    }
+    assert_eq!(pages, [true, false]);
+}
@@ -0,0 +1,18 @@
+// requests go through App::handle end to end; one module per feature
+
+mod acls;
+mod buckets;
+mod common;
+mod conditions;
+mod copy;
+mod cross_tenant;
+mod delete_objects;
+mod etags;
+mod head;
+mod keys;
+mod lifecycle;
+mod listing;
+mod metadata;
+mod policies;
+mod ranges;
+mod versioning;
@@ -0,0 +1,32 @@
+use hyper::StatusCode;
+
+use crate::common::{app, body, request, send, ALICE};
+
+#[tokio::test]
+async fn object_metadata_is_returned_and_overridable() {
+    let app = app("metadata").await;
+    let mut req = request(Some(ALICE), "PUT", "/alice-bucket/page", "<html>");
+    for (name, value) in [
+        ("content-type", "text/html"),
+        ("content-encoding", "aws-chunked,gzip"),
+        ("cache-control", "max-age=60"),
+        ("x-amz-meta-build", "42"),
+    ] {
+        req.headers_mut().insert(name, value.parse().unwrap());
+    }
+    app.clone().handle(req).await.unwrap();
+
+    let response = send(&app, None, "HEAD", "/alice-bucket/page", "").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = send(&app, Some(ALICE), "HEAD", "/alice-bucket/page", "").await;
+    let headers = response.headers();
+    assert_eq!(headers["content-type"], "text/html");
+    assert_eq!(headers["content-encoding"], "gzip");
+    assert_eq!(headers["cache-control"], "max-age=60");
+    assert_eq!(headers["x-amz-meta-build"], "42");
+
+    let uri = "/alice-bucket/page?response-content-type=text%2Fplain";
+    let response = send(&app, Some(ALICE), "GET", uri, "").await;
+    assert_eq!(response.headers()["content-type"], "text/plain");
+    assert_eq!(body(response).await, "<html>");
+}
@@ -0,0 +1,123 @@
+use hyper::StatusCode;
+
+use crate::common::{app, body, request, send, ALICE, BOB};
+
+#[tokio::test]
+async fn bucket_policy_grants_prefix_to_other_tenant() {
+    let app = app("policy").await;
+    let policy = r#"{
+        "Version": "2012-10-17",
+        "Statement": [{
+            "Sid": "CiArtifacts",
+            "Effect": "Allow",
+            "Principal": {"AWS": ["bob"]},
+            "Action": ["s3:PutObject", "s3:GetObject"],
+            "Resource": "arn:aws:s3:::alice-bucket/artifacts-*"
+        }, {
+            "Effect": "Allow",
+            "Principal": "*",
+            "Action": "s3:ListBucket",
+            "Resource": "arn:aws:s3:::alice-bucket",
+            "Condition": {"StringLike": {"s3:prefix": "artifacts-*"}}
+        }]
+    }"#;
+
+    let response = send(&app, Some(BOB), "PUT", "/alice-bucket?policy", policy).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = send(&app, Some(ALICE), "PUT", "/alice-bucket?policy", policy).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = send(&app, Some(ALICE), "GET", "/alice-bucket?policy", "").await;
+    assert!(body(response)
+        .await
+        .contains("arn:aws:s3:::alice-bucket/artifacts-*"));
+
+    for (user, method, uri, status) in [
+        (BOB, "PUT", "/alice-bucket/artifacts-build", StatusCode::OK),
+        (BOB, "GET", "/alice-bucket/artifacts-build", StatusCode::OK),
+        (
+            BOB,
+            "DELETE",
+            "/alice-bucket/artifacts-build",
+            StatusCode::FORBIDDEN,
+        ),
+        (BOB, "PUT", "/alice-bucket/report", StatusCode::FORBIDDEN),
+        (
+            BOB,
+            "GET",
+            "/alice-bucket?prefix=artifacts-",
+            StatusCode::OK,
+        ),
+        (
+            BOB,
+            "GET",
+            "/alice-bucket?prefix=secret/",
+            StatusCode::FORBIDDEN,
+        ),
+        (BOB, "GET", "/alice-bucket?policy", StatusCode::FORBIDDEN),
+        (
+            ALICE,
+            "DELETE",
+            "/alice-bucket?policy",
+            StatusCode::NO_CONTENT,
+        ),
+        (
+            BOB,
+            "PUT",
+            "/alice-bucket/artifacts-build",
+            StatusCode::FORBIDDEN,
+        ),
+        (ALICE, "GET", "/alice-bucket?policy", StatusCode::NOT_FOUND),
+    ] {
+        let response = send(&app, Some(user), method, uri, "artifact").await;
+        assert_eq!(response.status(), status, "{} {}", method, uri);
+    }
+}
+
+#[tokio::test]
+async fn bucket_policy_deny_overrides_owner_and_allows() {
+    let app = app("policy-deny").await;
+    let policy = r#"{
+        "Statement": [{
+            "Effect": "Allow",
+            "Principal": "*",
+            "Action": "s3:*",
+            "Resource": "arn:aws:s3:::alice-bucket/*"
+        }, {
+            "Effect": "Deny",
+            "Principal": "*",
+            "Action": ["s3:PutObject", "s3:DeleteObject"],
+            "Resource": "arn:aws:s3:::alice-bucket/*",
+            "Condition": {"NotIpAddress": {"aws:SourceIp": "10.0.0.0/8"}}
+        }]
+    }"#;
+    let response = send(&app, Some(ALICE), "PUT", "/alice-bucket?policy", policy).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    for (addr, status) in [
+        ("10.1.2.3:5000", StatusCode::OK),
+        ("192.168.1.1:5000", StatusCode::FORBIDDEN),
+    ] {
+        for user in [ALICE, BOB] {
+            let mut req = request(Some(user), "PUT", "/alice-bucket/report", "data");
+            req.extensions_mut()
+                .insert(addr.parse::<std::net::SocketAddr>().unwrap());
+            let response = app.clone().handle(req).await.unwrap();
+            assert_eq!(response.status(), status, "{} from {}", user.0, addr);
+        }
+    }
+
+    let response = send(&app, None, "GET", "/alice-bucket/report", "").await;
+    assert_eq!(body(response).await, "data");
+
+    for policy in [
+        "not json",
+        r#"{"Statement": [{"Effect": "Allow", "Principal": "*", "Action": "s3:GetObject", "Resource": "arn:aws:s3:::bob-bucket/*"}]}"#,
+        r#"{"Statement": [{"Effect": "Maybe", "Principal": "*", "Action": "s3:GetObject", "Resource": "arn:aws:s3:::alice-bucket/*"}]}"#,
+        r#"{"Statement": [{"Effect": "Allow", "Principal": "*", "Action": "s3:GetObject", "Resource": "arn:aws:s3:::alice-bucket/*", "Condition": {"DateGreaterThan": {"aws:CurrentTime": "2020-01-01"}}}]}"#,
+    ] {
+        let response = send(&app, Some(ALICE), "PUT", "/alice-bucket?policy", policy).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(body(response).await.contains("MalformedPolicy"));
+    }
+}
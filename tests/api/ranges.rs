@@ -0,0 +1,39 @@
+use hyper::StatusCode;
+
+use crate::common::{app, body, request, send, ALICE};
+
+#[tokio::test]
+async fn range_requests_return_partial_content() {
+    let app = app("range").await;
+    send(
+        &app,
+        Some(ALICE),
+        "PUT",
+        "/alice-bucket/digits",
+        "0123456789",
+    )
+    .await;
+
+    for (range, status, content) in [
+        ("bytes=2-4", StatusCode::PARTIAL_CONTENT, "234"),
+        ("bytes=7-", StatusCode::PARTIAL_CONTENT, "789"),
+        ("bytes=-3", StatusCode::PARTIAL_CONTENT, "789"),
+        ("bytes=4-2", StatusCode::OK, "0123456789"),
+        ("bytes=10-", StatusCode::RANGE_NOT_SATISFIABLE, ""),
+    ] {
+        let mut req = request(Some(ALICE), "GET", "/alice-bucket/digits", "");
+        req.headers_mut().insert("range", range.parse().unwrap());
+        let response = app.clone().handle(req).await.unwrap();
+        assert_eq!(response.status(), status, "{}", range);
+        if status != StatusCode::RANGE_NOT_SATISFIABLE {
+            assert_eq!(body(response).await, content, "{}", range);
+        }
+    }
+
+    let mut req = request(Some(ALICE), "GET", "/alice-bucket/digits", "");
+    req.headers_mut()
+        .insert("range", "bytes=1-2".parse().unwrap());
+    let response = app.clone().handle(req).await.unwrap();
+    assert_eq!(response.headers()["content-range"], "bytes 1-2/10");
+    assert_eq!(response.headers()["content-length"], "2");
+}
@@ -0,0 +1,46 @@
+use hyper::StatusCode;
+
+use crate::common::{app, body, send, ALICE, BOB};
+
+#[tokio::test]
+async fn versioning_keeps_overwritten_and_deleted_objects() {
+    let app = app("versioning").await;
+    let enable = "<VersioningConfiguration><Status>Enabled</Status></VersioningConfiguration>";
+    let response = send(&app, Some(BOB), "PUT", "/alice-bucket?versioning", enable).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = send(&app, Some(ALICE), "PUT", "/alice-bucket?versioning", enable).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let first = send(&app, Some(ALICE), "PUT", "/alice-bucket/doc", "v1").await;
+    let first = first.headers()["x-amz-version-id"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    send(&app, Some(ALICE), "PUT", "/alice-bucket/doc", "v2").await;
+
+    let uri = format!("/alice-bucket/doc?versionId={}", first);
+    let response = send(&app, Some(ALICE), "GET", &uri, "").await;
+    assert_eq!(body(response).await, "v1");
+
+    let response = send(&app, Some(ALICE), "DELETE", "/alice-bucket/doc", "").await;
+    assert_eq!(response.headers()["x-amz-delete-marker"], "true");
+    let marker = response.headers()["x-amz-version-id"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let response = send(&app, Some(ALICE), "GET", "/alice-bucket/doc", "").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let versions = body(send(&app, Some(ALICE), "GET", "/alice-bucket?versions", "").await).await;
+    assert_eq!(versions.matches("<Version>").count(), 2);
+    assert!(versions.contains(&format!(
+        "<DeleteMarker><Key>doc</Key><VersionId>{}</VersionId><IsLatest>true</IsLatest>",
+        marker
+    )));
+
+    let uri = format!("/alice-bucket/doc?versionId={}", marker);
+    let response = send(&app, Some(ALICE), "DELETE", &uri, "").await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = send(&app, Some(ALICE), "GET", "/alice-bucket/doc", "").await;
+    assert_eq!(body(response).await, "v2");
+}